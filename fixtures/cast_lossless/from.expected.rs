@@ -0,0 +1,7 @@
+fn widen(x: u8) -> u64 {
+    u64::from(x)
+}
+
+fn main() {
+    println!("{}", widen(42));
+}
@@ -0,0 +1,7 @@
+fn widen(x: u8) -> u64 {
+    x as u64
+}
+
+fn main() {
+    println!("{}", widen(42));
+}
@@ -0,0 +1,6 @@
+fn main() {
+    let x: u64 = 42;
+    if let Ok(y) = u32::try_from(x) {
+        println!("{y}");
+    }
+}
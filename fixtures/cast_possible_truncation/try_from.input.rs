@@ -0,0 +1,5 @@
+fn main() {
+    let x: u64 = 42;
+    let y = x as u32;
+    println!("{y}");
+}
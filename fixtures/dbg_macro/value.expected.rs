@@ -0,0 +1,6 @@
+fn main() {
+    let x = 21;
+    let y = x * 2;
+    let z = y;
+    println!("{y} {z}");
+}
@@ -0,0 +1,7 @@
+fn main() {
+    let x = 21;
+    let y = dbg!(x * 2);
+    let z = dbg!(y);
+    dbg!(&y);
+    println!("{y} {z}");
+}
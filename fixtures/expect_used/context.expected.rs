@@ -0,0 +1,10 @@
+fn home() -> Result<String, String> {
+    let home = std::env::var("HOME").map_err(|e| format!("HOME is not set: {e}"))?;
+    Ok(home)
+}
+
+fn main() {
+    if let Ok(home) = home() {
+        println!("{home}");
+    }
+}
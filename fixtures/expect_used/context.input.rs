@@ -0,0 +1,10 @@
+fn home() -> Result<String, String> {
+    let home = std::env::var("HOME").expect("HOME is not set");
+    Ok(home)
+}
+
+fn main() {
+    if let Ok(home) = home() {
+        println!("{home}");
+    }
+}
@@ -0,0 +1,4 @@
+fn main() {
+    let a = 0.1_f64 + 0.2;
+    println!("{}", (a - 0.3).abs() < f64::EPSILON);
+}
@@ -0,0 +1,5 @@
+fn main() {
+    let a: f32 = 0.1 + 0.2;
+    let b: f32 = 0.3;
+    println!("{}", a == b);
+}
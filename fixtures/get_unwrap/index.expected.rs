@@ -0,0 +1,8 @@
+fn main() {
+    let mut v = vec![1, 2, 3];
+    let first = &v[0];
+    println!("{first}");
+    let last = &mut v[2];
+    *last += 1;
+    println!("{v:?}");
+}
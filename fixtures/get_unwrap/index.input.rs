@@ -0,0 +1,8 @@
+fn main() {
+    let mut v = vec![1, 2, 3];
+    let first = v.get(0).unwrap();
+    println!("{first}");
+    let last = v.get_mut(2).unwrap();
+    *last += 1;
+    println!("{v:?}");
+}
@@ -0,0 +1,10 @@
+fn first(v: &[i32]) -> Option<i32> {
+    let x = v.first().unwrap();
+    Some(*x)
+}
+
+fn main() {
+    if let Some(x) = first(&[1, 2]) {
+        println!("{x}");
+    }
+}
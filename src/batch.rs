@@ -0,0 +1,290 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+#[cfg(unix)]
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::time::{Duration, Instant};
+
+use serde::Serialize;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    Succeeded,
+    Failed,
+    TimedOut,
+}
+
+impl std::fmt::Display for Status {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str(match self {
+            Status::Succeeded => "succeeded",
+            Status::Failed => "failed",
+            Status::TimedOut => "timed out",
+        })
+    }
+}
+
+// How the run on a repository went
+#[derive(Debug, Clone, Serialize)]
+pub struct Summary {
+    pub repo: String,
+    pub status: Status,
+    pub seconds: u64,
+    // the records contributed to the merged dataset
+    pub records: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+// the repositories of a batch: the lines of a file, or the git repositories under a directory
+pub fn repositories(batch: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut repos = Vec::new();
+    if batch.is_file() {
+        for line in std::fs::read_to_string(batch)?.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                repos.push(PathBuf::from(line));
+            }
+        }
+    } else {
+        find_repositories(batch, &mut repos)?;
+        repos.sort();
+    }
+    Ok(repos)
+}
+
+// a repository is not searched for nested ones, and neither are hidden or `target` folders
+fn find_repositories(dir: &Path, repos: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    if dir.join(".git").exists() {
+        repos.push(dir.to_path_buf());
+        return Ok(());
+    }
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        if path.is_dir() && !name.starts_with('.') && name != "target" {
+            find_repositories(&path, repos)?;
+        }
+    }
+    Ok(())
+}
+
+// the executable of the tool, to run on each repository in a process of its own
+fn executable() -> std::io::Result<PathBuf> {
+    let exe = std::env::current_exe()?;
+    // unit tests run from `target/debug/deps`, next to the binary
+    #[cfg(test)]
+    let exe = exe
+        .parent()
+        .and_then(|p| p.parent())
+        .map_or(exe.clone(), |p| p.join("rust-diagnostics"));
+    Ok(exe)
+}
+
+// the name of the files of a repository in the work folder of the batch
+fn file_name(repo: &Path) -> String {
+    repo.to_string_lossy()
+        .chars()
+        .map(|c| {
+            if c.is_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// the reason of the failure printed by the tool, e.g. `Cannot mine the commits HEAD: ...`
+fn failure(stdout: &str) -> Option<String> {
+    stdout
+        .lines()
+        .find(|l| l.starts_with("Cannot ") || l.starts_with("To use the "))
+        .map(|l| l.to_string())
+}
+
+// whether a file of the temporary directory has been left by the process, e.g. the snapshot
+// `rust-diagnostics-1234-0` or the scratch copy `rust-diagnostics-fix-1234-0` of the process 1234
+fn left_by(name: &str, pid: u32) -> bool {
+    let Some(rest) = name.strip_prefix("rust-diagnostics-") else {
+        return false;
+    };
+    let rest = rest
+        .strip_prefix("fix-")
+        .or_else(|| rest.strip_prefix("fixture-"))
+        .unwrap_or(rest);
+    let pid = pid.to_string();
+    rest == pid || rest.strip_prefix(&pid).is_some_and(|r| r.starts_with('-'))
+}
+
+// kill the tool with the `cargo` and `clippy-driver` processes it has started, which are in
+// its process group, and remove the snapshots and scratch copies they have left behind
+fn kill(child: &mut Child) {
+    #[cfg(unix)]
+    Command::new("kill")
+        .args(["-KILL", &format!("-{}", child.id())])
+        .stderr(Stdio::null())
+        .status()
+        .ok();
+    child.kill().ok();
+    child.wait().ok();
+    if let Ok(entries) = std::fs::read_dir(std::env::temp_dir()) {
+        for entry in entries.flatten() {
+            if left_by(&entry.file_name().to_string_lossy(), child.id()) {
+                std::fs::remove_dir_all(entry.path()).ok();
+            }
+        }
+    }
+}
+
+// run the tool with the arguments on the repository, until it exits or the timeout expires
+fn run_one(
+    repo: &Path,
+    args: &[String],
+    stdout: &Path,
+    stderr: &Path,
+    timeout: Duration,
+) -> (Status, Option<String>) {
+    if !repo.is_dir() {
+        return (Status::Failed, Some("Not a directory".to_string()));
+    }
+    let spawned = executable().and_then(|exe| {
+        let mut command = Command::new(exe);
+        command
+            .args(args)
+            .current_dir(repo)
+            .stdin(Stdio::null())
+            .stdout(File::create(stdout)?)
+            .stderr(File::create(stderr)?);
+        // in a process group of its own, to be killed with its children on timeout
+        #[cfg(unix)]
+        command.process_group(0);
+        command.spawn()
+    });
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(e) => return (Status::Failed, Some(e.to_string())),
+    };
+    let start = Instant::now();
+    loop {
+        match child.try_wait() {
+            Ok(Some(status)) => {
+                let output = std::fs::read_to_string(stdout).unwrap_or_default();
+                return match (status.success(), failure(&output)) {
+                    (true, None) => (Status::Succeeded, None),
+                    (_, Some(reason)) => (Status::Failed, Some(reason)),
+                    (false, None) => (Status::Failed, Some(status.to_string())),
+                };
+            }
+            Ok(None) if start.elapsed() < timeout => std::thread::sleep(Duration::from_millis(100)),
+            Ok(None) => {
+                kill(&mut child);
+                return (
+                    Status::TimedOut,
+                    Some(format!("Killed after {} seconds", timeout.as_secs())),
+                );
+            }
+            Err(e) => return (Status::Failed, Some(e.to_string())),
+        }
+    }
+}
+
+// merge the records of the dataset of a repository into the output, tagged with its path, and
+// return their number
+fn merge(dataset: &Path, repo: &str, merged: &mut File) -> std::io::Result<usize> {
+    let mut records = 0;
+    for line in BufReader::new(File::open(dataset)?).lines() {
+        if let Ok(serde_json::Value::Object(mut record)) = serde_json::from_str(&line?) {
+            record.insert("repo".to_string(), repo.into());
+            writeln!(merged, "{}", serde_json::Value::Object(record))?;
+            records += 1;
+        }
+    }
+    Ok(records)
+}
+
+// Run the tool with the arguments on each repository, keeping the outputs of every repository
+// in the folder `{output}.d` and merging them into the JSONL `output`: the mined fixes when
+// `mining`, or else the printed output of each repository, tagged with its path
+pub fn batch(
+    repos: &[PathBuf],
+    args: &[String],
+    mining: bool,
+    output: &Path,
+    timeout: Duration,
+) -> std::io::Result<Vec<Summary>> {
+    let mut work = output.as_os_str().to_owned();
+    work.push(".d");
+    let work = PathBuf::from(work);
+    std::fs::create_dir_all(&work)?;
+    let work = work.canonicalize()?;
+    let mut merged = File::create(output)?;
+    let mut summaries = Vec::new();
+    for repo in repos {
+        let name = file_name(repo);
+        let stdout = work.join(format!("{name}.out"));
+        let stderr = work.join(format!("{name}.log"));
+        let dataset = work.join(format!("{name}.jsonl"));
+        let mut args = args.to_vec();
+        if mining {
+            // kept across batches so that each repository resumes from its checkpoint
+            args.extend([
+                "--output".to_string(),
+                dataset.to_string_lossy().to_string(),
+            ]);
+        }
+        let start = Instant::now();
+        let (mut status, mut reason) = run_one(repo, &args, &stdout, &stderr, timeout);
+        let seconds = start.elapsed().as_secs();
+        let mut records = 0;
+        if status == Status::Succeeded {
+            let repo = repo.to_string_lossy().to_string();
+            // a repository whose outputs cannot be read fails without stopping the batch
+            let contributed = if mining {
+                merge(&dataset, &repo, &mut merged)
+                    .map_err(|e| format!("Cannot read {}: {e}", dataset.display()))
+            } else {
+                std::fs::read_to_string(&stdout)
+                    .map_err(|e| format!("Cannot read {}: {e}", stdout.display()))
+                    .and_then(|output| {
+                        let record = serde_json::json!({ "repo": repo, "output": output });
+                        writeln!(merged, "{record}").map_err(|e| e.to_string())?;
+                        Ok(1)
+                    })
+            };
+            match contributed {
+                Ok(n) => records = n,
+                Err(e) => (status, reason) = (Status::Failed, Some(e)),
+            }
+        }
+        summaries.push(Summary {
+            repo: repo.to_string_lossy().to_string(),
+            status,
+            seconds,
+            records,
+            reason,
+        });
+    }
+    let mut summary = File::create(work.join("summary.jsonl"))?;
+    for s in &summaries {
+        if let Ok(s) = serde_json::to_string(s) {
+            writeln!(summary, "{s}")?;
+        }
+    }
+    Ok(summaries)
+}
+
+pub fn print_summary(summaries: &[Summary]) {
+    println!("{:<30} {:<10} {:>8} reason", "repo", "status", "records");
+    for s in summaries {
+        println!(
+            "{:<30} {:<10} {:>8} {}",
+            s.repo,
+            s.status.to_string(),
+            s.records,
+            s.reason.as_deref().unwrap_or("-")
+        );
+    }
+}
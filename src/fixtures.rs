@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::rules::Registry;
+use crate::suggestion::preview;
+use crate::{fix_by_rules, to_diagnostic, Ran};
+
+const MANIFEST: &str = r#"[package]
+name = "fixture"
+version = "0.1.0"
+edition = "2021"
+"#;
+
+// A fixture of the rules of a lint: the input of the rules, e.g.
+// `fixtures/unwrap_used/if_let.input.rs` for `clippy::unwrap_used`, and the output they are
+// expected to make of it, `fixtures/unwrap_used/if_let.expected.rs`
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub lint: String,
+    pub name: String,
+    pub input: PathBuf,
+    pub expected: PathBuf,
+}
+
+// the fixtures under the folder, in the order of their lints and names
+pub fn fixtures(dir: &Path) -> std::io::Result<Vec<Fixture>> {
+    let mut fixtures = Vec::new();
+    for entry in std::fs::read_dir(dir)?.flatten() {
+        if !entry.path().is_dir() {
+            continue;
+        }
+        let lint = entry.file_name().to_string_lossy().to_string();
+        for file in std::fs::read_dir(entry.path())?.flatten() {
+            let input = file.path();
+            let file_name = file.file_name().to_string_lossy().to_string();
+            if let Some(name) = file_name.strip_suffix(".input.rs") {
+                fixtures.push(Fixture {
+                    lint: lint.clone(),
+                    name: name.to_string(),
+                    expected: entry.path().join(format!("{name}.expected.rs")),
+                    input,
+                });
+            }
+        }
+    }
+    fixtures.sort_by(|a, b| (&a.lint, &a.name).cmp(&(&b.lint, &b.name)));
+    Ok(fixtures)
+}
+
+// the warnings of the crate in the folder, diagnosed with the lint of the fixture, and whether
+// the crate builds
+fn diagnose(dir: &Path, lint: &str) -> (Vec<Ran>, bool) {
+    let mut map = HashMap::new();
+    let args = vec![
+        "clippy".to_string(),
+        "--message-format=json".to_string(),
+        "--".to_string(),
+        format!("-W{lint}"),
+    ];
+    let build = to_diagnostic(&mut map, args, dir);
+    let warnings = map.remove("src/main.rs").unwrap_or_default();
+    (warnings, build.analysable())
+}
+
+// the lints of the warnings, with the number of times each of them is reported
+fn counts(warnings: &[Ran]) -> HashMap<&str, usize> {
+    let mut counts = HashMap::new();
+    for w in warnings {
+        *counts.entry(w.lint()).or_default() += 1;
+    }
+    counts
+}
+
+// Run the rules of the lint of the fixture over its input, in a crate of its own in the
+// folder, and tell why the fixture fails: its output differs from the expected one, clippy
+// still reports the lint on it, or reports lints which it did not report on the input
+pub fn test(fixture: &Fixture, registry: &Registry, dir: &Path) -> Result<(), String> {
+    let lint = format!("clippy::{}", fixture.lint);
+    let rules = registry.rules(&lint);
+    if rules.is_empty() {
+        return Err(format!("there are no rules of {lint}"));
+    }
+    let input = std::fs::read_to_string(&fixture.input).map_err(|e| e.to_string())?;
+    let expected = std::fs::read_to_string(&fixture.expected)
+        .map_err(|e| format!("cannot read {}: {e}", fixture.expected.display()))?;
+    let main = dir.join("src/main.rs");
+    std::fs::create_dir_all(dir.join("src")).map_err(|e| e.to_string())?;
+    std::fs::write(dir.join("Cargo.toml"), MANIFEST).map_err(|e| e.to_string())?;
+    std::fs::write(&main, &input).map_err(|e| e.to_string())?;
+    let (before, _) = diagnose(dir, &lint);
+    let flagged: Vec<Ran> = before
+        .iter()
+        .filter(|w| w.lint() == lint)
+        .cloned()
+        .collect();
+    if flagged.is_empty() {
+        return Err(format!("{lint} is not reported on the input"));
+    }
+    fix_by_rules(&main, &flagged, rules);
+    let output = std::fs::read_to_string(&main).map_err(|e| e.to_string())?;
+    if output != expected {
+        let file = fixture.expected.display().to_string();
+        return Err(format!(
+            "the output differs from {file}:\n{}",
+            preview(&file, &expected, &output).unwrap_or(output)
+        ));
+    }
+    let (after, analysable) = diagnose(dir, &lint);
+    if !analysable {
+        return Err("the output does not compile".to_string());
+    }
+    if after.iter().any(|w| w.lint() == lint) {
+        return Err(format!("{lint} is still reported on the output"));
+    }
+    let (before, after) = (counts(&before), counts(&after));
+    let mut introduced: Vec<&str> = after
+        .iter()
+        .filter(|(l, n)| before.get(*l).is_none_or(|m| *n > m))
+        .map(|(l, _)| *l)
+        .collect();
+    introduced.sort();
+    if !introduced.is_empty() {
+        return Err(format!("the output introduces {}", introduced.join(", ")));
+    }
+    Ok(())
+}
+
+// Test the rules of the registry on every fixture under the folder, printing whether each of
+// them passes, and return the number of those which pass
+pub fn test_all(fixtures: &[Fixture], registry: &Registry) -> usize {
+    let dir = std::env::temp_dir().join(format!("rust-diagnostics-fixture-{}", std::process::id()));
+    let mut passed = 0;
+    for f in fixtures {
+        match test(f, registry, &dir) {
+            Ok(()) => {
+                passed += 1;
+                println!("pass {}/{}", f.lint, f.name);
+            }
+            Err(e) => println!("fail {}/{}: {e}", f.lint, f.name),
+        }
+    }
+    std::fs::remove_dir_all(&dir).ok();
+    passed
+}
@@ -1,43 +1,62 @@
-#![feature(internal_output_capture)]
-use std::sync::Arc;
-use cargo_metadata::{diagnostic::Diagnostic, Message};
+#![cfg_attr(test, feature(internal_output_capture))]
+use cargo_metadata::{
+    diagnostic::{Diagnostic, DiagnosticLevel, DiagnosticSpanLine},
+    Message,
+};
 use serde::Serialize;
 use std::{
     collections::HashMap,
     fs::read_to_string,
-    path::PathBuf,
+    path::Path,
     process::{Command, Stdio},
 };
-#[cfg(patch)]
-mod patch {
-    use git2::{Commit, DiffOptions, ObjectType, Repository, Signature, Time};
-    use git2::{DiffFormat, Error, Pathspec};
-}
 
-#[cfg(fix)]
+mod batch;
+#[cfg(feature = "fix")]
+mod fixtures;
+#[cfg(feature = "fix")]
 mod language;
+#[cfg(feature = "patch")]
+mod mine;
+#[cfg(feature = "patch")]
+mod outcome;
+#[cfg(feature = "fix")]
+mod pattern;
+#[cfg(feature = "patch")]
+mod provenance;
+#[cfg(feature = "patch")]
+mod revision;
+#[cfg(feature = "fix")]
+mod rewrite;
+#[cfg(feature = "fix")]
+mod rules;
+#[cfg(feature = "fix")]
+mod scratch;
+#[cfg(feature = "fix")]
+mod suggestion;
+#[cfg(feature = "patch")]
+mod suppression;
+#[cfg(feature = "patch")]
+mod toolchain;
+#[cfg(feature = "fix")]
+mod transform;
 
-#[cfg(fix)]
-mod fix {
-    use itertools::Itertools;
-    use std::num::Wrapping;
-    use tree_sitter::QueryCursor;
-    use tree_sitter_parsers::parse;
-}
+#[cfg(feature = "fix")]
+use std::num::Wrapping;
 
-#[cfg(rustc_flags)]
-mod rustc_flags {
-    use cargo::util::command_prelude::{ArgMatchesExt, Config};
-    use cargo::{
-        core::compiler::{CompileKind, RustcTargetData},
-        util::command_prelude::{CompileMode, ProfileChecking},
-    };
-    use clap::Arg;
-}
+#[cfg(feature = "rustc_flags")]
+use cargo::util::command_prelude::{ArgMatchesExt, Config};
+#[cfg(feature = "rustc_flags")]
+use cargo::{
+    core::compiler::{CompileKind, RustcTargetData},
+    util::command_prelude::{CompileMode, ProfileChecking},
+};
+#[cfg(feature = "rustc_flags")]
+use clap::Arg;
 
 use structopt::StructOpt;
 
-#[derive(StructOpt)]
+#[derive(StructOpt, Default)]
 struct Args {
     #[structopt(name = "flags", long)]
     /// warnings concerning the warning flags
@@ -45,9 +64,134 @@ struct Args {
     #[structopt(name = "patch", long)]
     /// reduce patch id to hunks that may be relevant to the warnings
     patch: Option<String>,
+    #[structopt(name = "from", long)]
+    /// compare from this revision instead of the work tree: a commit, branch or tag, `HEAD~3`,
+    /// `INDEX` for the staged changes or `WORKTREE` for the files on disk
+    from: Option<String>,
+    #[structopt(name = "to", long)]
+    /// compare to this revision, in the same syntax as `--from` [default: WORKTREE]
+    to: Option<String>,
     #[structopt(name = "confirm", long)]
     /// confirm whether the related warnings of current revision are indeed fixed by the patch
     confirm: bool,
+    #[structopt(name = "classify", long, possible_values = &["table", "json"])]
+    /// classify what became of every warning of the first revision in the second one, and
+    /// list the introduced warnings, as a `table` or as `json`
+    classify: Option<String>,
+    #[structopt(name = "retry-check", long)]
+    /// retry a revision that clippy fails to build with `cargo check`, to capture at least
+    /// its compiler errors
+    retry_check: bool,
+    #[structopt(name = "provenance", long, possible_values = &["table", "json"])]
+    /// blame the lines of every warning for the commit, the date and the age in days since
+    /// which it has existed, with a histogram of the ages by lint, as a `table` or as `json`
+    provenance: Option<String>,
+    #[structopt(name = "include-suppressed", long)]
+    /// keep the warnings silenced by an added `#[allow]`, `#![allow]` or lint configuration
+    /// among the fixes
+    include_suppressed: bool,
+    #[structopt(name = "mine", long)]
+    /// mine the fixes of warnings made by every commit of a range, e.g. `HEAD` for the whole
+    /// history or `v0.1..main`, into a JSONL dataset
+    mine: Option<String>,
+    #[structopt(name = "errors", long)]
+    /// mine the compiler errors (E-codes) of the parents that the commits resolve, instead of
+    /// the warnings that they fix
+    errors: bool,
+    #[structopt(name = "output", long)]
+    /// the JSONL file of `--mine` [default: fixes.jsonl], resumed from its checkpoint when it
+    /// has been interrupted, or the merged JSONL file of `--batch` [default: dataset.jsonl]
+    output: Option<String>,
+    #[structopt(name = "batch", long)]
+    /// run with the other options on every git repository under a directory, or listed one
+    /// per line in a file, and merge their outputs
+    batch: Option<String>,
+    #[structopt(name = "timeout", long, default_value = "1800")]
+    /// the seconds after which the run of `--batch` on a repository is stopped
+    timeout: u64,
+    #[structopt(name = "test-fixes", long)]
+    /// validate every transformation of the `fix` feature with `cargo test`, in addition to
+    /// `cargo check`
+    test_fixes: bool,
+    #[structopt(name = "atomic", long)]
+    /// fix one warning at a time by applying its own suggestion only, rather than fixing all
+    /// of the warnings with `clippy --fix`
+    atomic: bool,
+    #[structopt(
+        name = "applicability",
+        long,
+        default_value = "machine-applicable",
+        possible_values = &[
+            "machine-applicable",
+            "maybe-incorrect",
+            "has-placeholders",
+            "unspecified"
+        ]
+    )]
+    /// apply the suggestions up to this applicability, from the safest to the least safe
+    applicability: String,
+    #[structopt(name = "dry-run", long)]
+    /// print the changes the suggestions would make, without transforming anything
+    dry_run: bool,
+    #[structopt(name = "rules", long)]
+    /// load the rules of the lints from this folder, e.g. `unwrap_used.txl` or `unwrap_used.ssr`
+    /// for `clippy::unwrap_used`, instead of the native and bundled ones of their lints
+    rules: Option<String>,
+    #[structopt(name = "list-rules", long)]
+    /// list the lints which have rules to transform their warnings
+    list_rules: bool,
+    #[structopt(name = "test-rules", long)]
+    /// test the rules of every lint on its fixtures in this folder, e.g.
+    /// `unwrap_used/if_let.input.rs` to be transformed into `unwrap_used/if_let.expected.rs`
+    test_rules: Option<String>,
+}
+
+impl Args {
+    // the options to run the tool with on each repository of a batch
+    fn forwarded(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        for flag in &self.flags {
+            args.extend(["--flags".to_string(), flag.clone()]);
+        }
+        for (name, value) in [
+            ("--patch", &self.patch),
+            ("--from", &self.from),
+            ("--to", &self.to),
+            ("--classify", &self.classify),
+            ("--mine", &self.mine),
+            ("--provenance", &self.provenance),
+            ("--rules", &self.rules),
+        ] {
+            if let Some(value) = value {
+                args.extend([name.to_string(), value.clone()]);
+            }
+        }
+        if self.confirm {
+            args.push("--confirm".to_string());
+        }
+        if self.include_suppressed {
+            args.push("--include-suppressed".to_string());
+        }
+        if self.retry_check {
+            args.push("--retry-check".to_string());
+        }
+        if self.errors {
+            args.push("--errors".to_string());
+        }
+        if self.test_fixes {
+            args.push("--test-fixes".to_string());
+        }
+        if self.atomic {
+            args.push("--atomic".to_string());
+        }
+        if !self.applicability.is_empty() {
+            args.extend(["--applicability".to_string(), self.applicability.clone()]);
+        }
+        if self.dry_run {
+            args.push("--dry-run".to_string());
+        }
+        args
+    }
 }
 
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -57,6 +201,13 @@ struct Ran {
     end: usize,
     suggestion: String,
     note: String,
+    // the replacements suggested by the children of the diagnostic, e.g. the `help` of clippy
+    suggestions: Vec<Suggested>,
+    // the highlighted source code of the span, to recognise it after it has been moved
+    code: String,
+    // the message as printed by the compiler, and the long explanation of its error code
+    rendered: String,
+    explanation: String,
     start_line: usize,
     end_line: usize,
     // start_column: usize,
@@ -64,6 +215,54 @@ struct Ran {
     fixed: bool,
 }
 
+// A replacement suggested by a child of a diagnostic for one of its spans
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Serialize)]
+struct Suggested {
+    message: String,
+    file: String,
+    start_line: usize,
+    end_line: usize,
+    replacement: String,
+    // e.g. `MachineApplicable`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    applicability: Option<String>,
+}
+
+impl Ran {
+    // the lint of the diagnostic, e.g. `clippy::unwrap_used` for `#[Warning(clippy::unwrap_used)`
+    #[cfg(any(feature = "patch", feature = "fix"))]
+    fn lint(&self) -> &str {
+        match self.name.split_once('(') {
+            Some((_, lint)) => lint.trim_end_matches(')'),
+            None => &self.name,
+        }
+    }
+
+    // whether it is a compiler error, e.g. `#[Error(E0382)`
+    #[cfg(feature = "patch")]
+    fn is_error(&self) -> bool {
+        self.name.starts_with("#[Error(")
+    }
+}
+
+// How the build of a revision went: the warnings of a revision that does not build are
+// incomplete, so that the missing ones must not be taken as fixed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Build {
+    // reported by the `BuildFinished` message of cargo
+    success: bool,
+    // the number of error-level messages
+    errors: usize,
+    // the messages come from `cargo check`, after clippy has failed
+    checked: bool,
+}
+
+impl Build {
+    fn analysable(&self) -> bool {
+        self.success && self.errors == 0
+    }
+}
+
 // insert diagnostic code as an markup element around the code causing the diagnostic message
 fn markup(source: &[u8], map: Vec<Ran>) -> Vec<u8> {
     let mut output = Vec::new();
@@ -76,7 +275,7 @@ fn markup(source: &[u8], map: Vec<Ran>) -> Vec<u8> {
             if m.end == i {
                 output.extend(
                     format!(
-                        "/*\n{}{}{}*/",
+                        "/*\n{}{}{}{}*/",
                         m.name,
                         if m.suggestion == "None" {
                             "".to_string()
@@ -86,6 +285,10 @@ fn markup(source: &[u8], map: Vec<Ran>) -> Vec<u8> {
                                 m.suggestion.replace("\\n", "\n").replace('\"', "")
                             )
                         },
+                        m.suggestions
+                            .iter()
+                            .map(|s| format!("\nsuggestion: {}: {}", s.message, s.replacement))
+                            .collect::<String>(),
                         if m.note == "None" {
                             "".to_string()
                         } else {
@@ -101,7 +304,7 @@ fn markup(source: &[u8], map: Vec<Ran>) -> Vec<u8> {
     output
 }
 
-#[cfg(fix)]
+#[cfg(feature = "fix")]
 // list the relevant rules as comments
 fn markup_rules(start: Wrapping<usize>, end: Wrapping<usize>, map: Vec<Ran>) -> Vec<u8> {
     let mut output = Vec::new();
@@ -113,95 +316,29 @@ fn markup_rules(start: Wrapping<usize>, end: Wrapping<usize>, map: Vec<Ran>) ->
     output
 }
 
-#[derive(Debug, Serialize, PartialEq, Eq, PartialOrd, Ord)]
-pub struct ExtractedNode<'query> {
-    name: &'query str,
-    start_byte: usize,
-    end_byte: usize,
-}
-
-#[cfg(fix)]
-mod fix {
-    use anyhow::Result;
-    // Split up the Rust source_file into individual items, indiced by their start_byte offsets
-    fn splitup(source: &[u8]) -> Result<HashMap<usize, &[u8]>> {
-        let mut output: HashMap<usize, &[u8]> = HashMap::new();
-        if let Ok(s) = std::str::from_utf8(source) {
-            let tree = parse(s, "rust");
-            if let Ok(query) = language::Language::Rust.parse_query(
-                "([
-      (const_item) @fn
-      (macro_invocation) @fn
-      (macro_definition) @fn
-      (empty_statement) @fn
-      (attribute_item) @fn
-      (inner_attribute_item) @fn
-      (mod_item) @fn
-      (foreign_mod_item) @fn
-      (struct_item) @fn
-      (union_item) @fn
-      (enum_item) @fn
-      (type_item) @fn
-      (function_item) @fn
-      (function_signature_item) @fn
-      (impl_item) @fn
-      (trait_item) @fn
-      (associated_type) @fn
-      (let_declaration) @fn
-      (use_declaration) @fn
-      (extern_crate_declaration) @fn
-      (static_item) @fn
-            ])",
-            ) {
-                let captures = query.capture_names().to_vec();
-                let mut cursor = QueryCursor::new();
-                let extracted = cursor
-                    .matches(&query, tree.root_node(), source)
-                    .flat_map(|query_match| query_match.captures)
-                    .map(|capture| {
-                        if let Ok(idx) = usize::try_from(capture.index) {
-                            let name = &captures[idx];
-                            let node = capture.node;
-                            Ok(ExtractedNode {
-                                name,
-                                start_byte: node.start_byte(),
-                                end_byte: node.end_byte(),
-                            })
-                        } else {
-                            Ok(ExtractedNode {
-                                name: "",
-                                start_byte: 0,
-                                end_byte: 0,
-                            })
-                        }
-                    })
-                    .collect::<Result<Vec<ExtractedNode>>>()?;
-                for m in extracted {
-                    if m.name == "fn" {
-                        if let Ok(code) = std::str::from_utf8(&source[m.start_byte..m.end_byte]) {
-                            output.insert(m.start_byte, code.as_bytes());
-                        }
-                    }
-                }
-            }
-        }
-        Ok(output)
-    }
-    // restore the original file
-    fn restore_original(file_name: &String, content: &String) {
-        std::fs::write(file_name, content).ok();
-    }
+#[cfg(feature = "fix")]
+// restore the original file
+fn restore_original(file_name: &Path, content: &String) {
+    std::fs::write(file_name, content).ok();
 }
-fn to_diagnostic(map: &mut HashMap<String, Vec<Ran>>, args: Vec<String>) {
+fn to_diagnostic(map: &mut HashMap<String, Vec<Ran>>, args: Vec<String>, dir: &Path) -> Build {
+    let mut build = Build::default();
     if let Ok(mut command) = Command::new("cargo")
         .args(args)
+        .current_dir(dir)
         .stdout(Stdio::piped())
         .spawn()
     {
         if let Some(take) = command.stdout.take() {
             let reader = std::io::BufReader::new(take);
             for message in cargo_metadata::Message::parse_stream(reader).flatten() {
+                if let Message::BuildFinished(finished) = &message {
+                    build.success = finished.success;
+                }
                 if let Message::CompilerMessage(msg) = message {
+                    if msg.message.level == DiagnosticLevel::Error {
+                        build.errors += 1;
+                    }
                     for s in msg.message.spans {
                         if let Ok(x) = usize::try_from(s.byte_start) {
                             if let Ok(y) = usize::try_from(s.byte_end) {
@@ -220,6 +357,13 @@ fn to_diagnostic(map: &mut HashMap<String, Vec<Ran>>, args: Vec<String>) {
                                         // end_column: s.column_end,
                                         suggestion: format!("{:?}", s.suggested_replacement),
                                         note: format!("{:?}", sub_messages(&msg.message.children)),
+                                        suggestions: sub_suggestions(&msg.message.children),
+                                        code: highlighted(&s.text),
+                                        rendered: msg.message.rendered.clone().unwrap_or_default(),
+                                        explanation: message_code
+                                            .explanation
+                                            .clone()
+                                            .unwrap_or_default(),
                                         fixed: false,
                                     };
                                     let filename = s.file_name;
@@ -239,9 +383,10 @@ fn to_diagnostic(map: &mut HashMap<String, Vec<Ran>>, args: Vec<String>) {
         }
         command.wait().ok();
     }
+    build
 }
 
-#[cfg(rustc_flags)]
+#[cfg(feature = "rustc_flags")]
 // Find all the RUSTC_FLAGS enabled by `cargo`
 // Adapted from https://github.com/rust-lang/cargo/blob/master/src/bin/cargo/commands/build.rs
 fn rustflags() -> Vec<String> {
@@ -265,22 +410,42 @@ fn rustflags() -> Vec<String> {
     target_info.rustflags.clone()
 }
 
-// markup all warnings into diagnostics
-fn diagnose_all_warnings(flags: Vec<String>) -> HashMap<String, Vec<Ran>> {
-    let mut args = vec![
-        "clippy".to_string(),
-        "--message-format=json".to_string(),
-        "--".to_string(),
-    ];
+// markup all warnings of the crate in `dir` into `dir/diagnostics`, building into
+// `target_dir` when it is given. When clippy fails to build the crate and `retry_check` is
+// set, `cargo check` is run instead to capture at least the compiler errors.
+fn diagnose_all_warnings(
+    flags: Vec<String>,
+    dir: &Path,
+    target_dir: Option<&Path>,
+    toolchain: Option<&str>,
+    retry_check: bool,
+) -> (HashMap<String, Vec<Ran>>, Build) {
+    let cargo = |subcommand: &str| {
+        let mut args = vec![subcommand.to_string(), "--message-format=json".to_string()];
+        if let Some(t) = toolchain {
+            args.insert(0, format!("+{t}"));
+        }
+        if let Some(t) = target_dir {
+            args.push(format!("--target-dir={}", t.display()));
+        }
+        args
+    };
+    let mut args = cargo("clippy");
+    args.push("--".to_string());
     for flag in flags {
         args.push(format!("-Wclippy::{}", flag));
     }
     let mut map: HashMap<String, Vec<Ran>> = HashMap::new();
-    to_diagnostic(&mut map, args);
+    let mut build = to_diagnostic(&mut map, args, dir);
+    if retry_check && !build.analysable() {
+        map.clear();
+        build = to_diagnostic(&mut map, cargo("check"), dir);
+        build.checked = true;
+    }
     if !map.is_empty() {
         let mut markup_map: HashMap<String, String> = HashMap::new();
         for file in map.keys() {
-            if let Ok(source) = read_to_string(file) {
+            if let Ok(source) = read_to_string(dir.join(file)) {
                 if let Some(v) = map.get(file) {
                     let markedup = &markup(source.as_bytes(), v.to_vec());
                     if let Ok(s) = std::str::from_utf8(markedup) {
@@ -291,7 +456,7 @@ fn diagnose_all_warnings(flags: Vec<String>) -> HashMap<String, Vec<Ran>> {
         }
         for file in map.keys() {
             let markedup = &markup_map[file];
-            let file_name = PathBuf::from("diagnostics").join(file);
+            let file_name = dir.join("diagnostics").join(file);
             // println!("Marked warning(s) into {:?}", &file_name);
             if let Some(p) = file_name.parent() {
                 if !p.exists() {
@@ -301,12 +466,33 @@ fn diagnose_all_warnings(flags: Vec<String>) -> HashMap<String, Vec<Ran>> {
             std::fs::write(&file_name, markedup).ok();
         }
     }
-    map
+    (map, build)
 }
 
-#[cfg(fix)]
-// process warnings from one RUSTC_FLAG at a time
-fn fix_warnings(flags: Vec<String>, map: &HashMap<String, Vec<Ran>>) {
+#[cfg(feature = "fix")]
+// process warnings from one RUSTC_FLAG at a time, transforming a scratch copy of the workspace
+// so that the user's files are never modified, not even when a transformation fails halfway.
+// The suggestions of the diagnostics up to the applicability are applied natively, all at once
+// or, being `atomic`, each on its own; a `dry_run` only prints the changes they would make.
+// The lints which have rules in the registry are transformed by TXL instead.
+fn fix_warnings(
+    flags: Vec<String>,
+    map: &HashMap<String, Vec<Ran>>,
+    test_fixes: bool,
+    (atomic, applicability, dry_run): (bool, &str, bool),
+    registry: &rules::Registry,
+) {
+    let flags: Vec<String> = flags.iter().map(|f| format!("-Wclippy::{}", f)).collect();
+    std::fs::remove_file(MANIFEST).ok();
+    let scratch = match scratch::Scratch::copy(Path::new(".")) {
+        Ok(scratch) => scratch,
+        Err(e) => {
+            println!("Cannot copy the workspace to fix the warnings: {e}");
+            return;
+        }
+    };
+    let mut validator = None;
+    let mut suggestions = None;
     for flag in &flags {
         let mut flagged_map: HashMap<String, Vec<Ran>> = HashMap::new();
         for file in map.keys() {
@@ -314,15 +500,8 @@ fn fix_warnings(flags: Vec<String>, map: &HashMap<String, Vec<Ran>>) {
                 let mut new_v = Vec::new();
                 for r in v {
                     let rule = &flag[2..];
-                    if r.name == format!("#[Warning({})", &rule) {
-                        let r1 = Ran {
-                            name: r.name.clone(),
-                            start: r.start,
-                            end: r.end,
-                            suggestion: r.suggestion.clone(),
-                            note: r.note.clone(),
-                        };
-                        new_v.push(r1);
+                    if r.name == format!("#[Warning({})", rule) {
+                        new_v.push(r.clone());
                     }
                 }
                 if !new_v.is_empty() {
@@ -331,8 +510,14 @@ fn fix_warnings(flags: Vec<String>, map: &HashMap<String, Vec<Ran>>) {
             }
         }
         if !flagged_map.is_empty() {
+            let lint = &flag[2..];
+            // collected before the rules rewrite any file of the scratch copy, so that their
+            // offsets hold in the original sources, which it is restored to after each flag
+            let suggestions =
+                suggestions.get_or_insert_with(|| suggestion::suggestions(&scratch.dir, &flags));
             let mut origin_map: HashMap<String, String> = HashMap::new();
             let mut markup_map: HashMap<String, String> = HashMap::new();
+            let mut tools: HashMap<String, transform::Tool> = HashMap::new();
             for file in flagged_map.keys() {
                 if let Ok(source) = read_to_string(file) {
                     if let Some(v) = flagged_map.get(file) {
@@ -343,89 +528,236 @@ fn fix_warnings(flags: Vec<String>, map: &HashMap<String, Vec<Ran>>) {
                         }
                     }
                 }
-                if flag == "-Wclippy::unwrap_used" {
-                    fix_unwrap_used(file);
+                if !registry.rules(lint).is_empty() && !atomic {
+                    let path = scratch.path(file);
+                    if let Some(tool) =
+                        fix_by_rules(&path, &flagged_map[file], registry.rules(lint))
+                    {
+                        if read_to_string(path).ok() != origin_map.get(file).cloned() {
+                            tools.insert(file.to_string(), tool);
+                        }
+                    }
                 }
             }
-            let mut args = vec![
-                "clippy".to_string(),
-                "--message-format=json".to_string(),
-                "--fix".to_string(),
-                "--allow-dirty".to_string(),
-                "--allow-no-vcs".to_string(),
-                "--broken-code".to_string(),
-                "--".to_string(),
-            ];
-            for flag in &flags {
-                args.push(flag.to_string());
-            }
-            let mut fixed_map: HashMap<String, Vec<Ran>> = HashMap::new();
-            to_diagnostic(&mut fixed_map, args);
-            for file in flagged_map.keys() {
-                if let Ok(source) = read_to_string(file) {
+            let threshold = suggestion::threshold(applicability);
+            // a file of the warnings may be missing from the work tree, e.g. when it has been
+            // renamed after the revision diagnosed by `--from`
+            let mut files: Vec<&String> = flagged_map
+                .keys()
+                .filter(|f| origin_map.contains_key(*f))
+                .collect();
+            files.sort();
+            if atomic {
+                for file in files {
                     let input = &origin_map[file];
-                    let output = source.as_bytes();
-                    if let Some(warnings) = flagged_map.get(file) {
-                        if let Some(fixes) = fixed_map.get(file) {
-                            let mut fixed_warnings = Vec::new();
-                            let mut remaining_warnings = Vec::new();
-                            for w in warnings {
-                                let mut found = false;
-                                for f in fixes {
-                                    if w.name == f.name {
-                                        found = true;
-                                        remaining_warnings.push(f.clone());
-                                        break;
-                                    }
-                                }
-                                if !found {
-                                    fixed_warnings.push(w.clone());
-                                }
+                    for w in &flagged_map[file] {
+                        let plan = suggestion::plan(
+                            file,
+                            suggestions.iter().filter(|s| {
+                                (s.start, s.end) == (w.start, w.end) && s.lint == w.lint()
+                            }),
+                            threshold,
+                        );
+                        if plan.accepted.is_empty() {
+                            continue;
+                        }
+                        let output = suggestion::apply(file, input, &plan.accepted);
+                        if dry_run {
+                            preview(file, input, &output);
+                            continue;
+                        }
+                        let validator = validator.get_or_insert_with(|| {
+                            transform::Validator::new(&scratch.dir, test_fixes)
+                        });
+                        to_fix(
+                            (flag, Some(w.start)),
+                            file,
+                            vec![w.clone()],
+                            (vec![w.clone()], Vec::new()),
+                            (input, output.as_bytes()),
+                            transform::Tool::Clippy,
+                            validator,
+                        );
+                    }
+                }
+                continue;
+            }
+            // the warnings remaining in the files rewritten by the rules, diagnosed again to tell
+            // which ones they have fixed, and for which the suggestions made on the original
+            // sources no longer hold
+            let mut rewritten_map: HashMap<String, Vec<Ran>> = HashMap::new();
+            if !tools.is_empty() {
+                let mut args = vec![
+                    "clippy".to_string(),
+                    "--message-format=json".to_string(),
+                    "--".to_string(),
+                ];
+                args.extend(flags.iter().cloned());
+                to_diagnostic(&mut rewritten_map, args, &scratch.dir);
+            }
+            let mut output_map: HashMap<String, (String, Vec<Ran>, Vec<Ran>)> = HashMap::new();
+            for file in &files {
+                let input = &origin_map[*file];
+                let warnings = &flagged_map[*file];
+                if tools.contains_key(*file) {
+                    if let Ok(source) = read_to_string(scratch.path(file)) {
+                        // all of the warnings of a file may have been fixed; those remaining
+                        // are still reported on the same code
+                        let fixes = rewritten_map.get(*file).cloned().unwrap_or_default();
+                        let mut fixed_warnings = Vec::new();
+                        let mut remaining_warnings = Vec::new();
+                        for w in warnings {
+                            match fixes.iter().find(|f| w.name == f.name && w.code == f.code) {
+                                Some(f) => remaining_warnings.push(f.clone()),
+                                None => fixed_warnings.push(w.clone()),
                             }
-                            to_fix(
-                                &flag,
-                                file,
-                                warnings.to_vec(),
-                                fixed_warnings.clone(),
-                                remaining_warnings.clone(),
-                                input,
-                                output,
-                            );
                         }
+                        output_map.insert(
+                            file.to_string(),
+                            (source, fixed_warnings, remaining_warnings),
+                        );
                     }
+                    // validate each transformation on the original sources, and start the next
+                    // flag from them too
+                    restore_original(&scratch.path(file), input);
+                    continue;
                 }
+                let plan = suggestion::plan(
+                    file,
+                    suggestions.iter().filter(|s| s.lint == lint),
+                    threshold,
+                );
+                for c in &plan.conflicts {
+                    println!(
+                        "Skipped the suggestion to {} of {} at {}:{}, which overlaps another one",
+                        c.message, c.lint, c.file, c.line
+                    );
+                }
+                let output = suggestion::apply(file, input, &plan.accepted);
+                let (fixed_warnings, remaining_warnings): (Vec<Ran>, Vec<Ran>) =
+                    warnings.iter().cloned().partition(|w| plan.fixes(w));
+                let remaining_warnings = remaining_warnings
+                    .iter()
+                    .map(|w| suggestion::relocate(w, &plan, &output))
+                    .collect();
+                output_map.insert(
+                    file.to_string(),
+                    (output, fixed_warnings, remaining_warnings),
+                );
             }
-            for file in flagged_map.keys() {
-                let input = &origin_map[file];
-                restore_original(file, input);
+            for file in files {
+                if let Some((output, fixed_warnings, remaining_warnings)) = output_map.get(file) {
+                    let input = &origin_map[file];
+                    if dry_run {
+                        preview(file, input, output);
+                        continue;
+                    }
+                    let validator = validator
+                        .get_or_insert_with(|| transform::Validator::new(&scratch.dir, test_fixes));
+                    to_fix(
+                        (flag, None),
+                        file,
+                        flagged_map[file].to_vec(),
+                        (fixed_warnings.clone(), remaining_warnings.clone()),
+                        (input, output.as_bytes()),
+                        tools.get(file).copied().unwrap_or(transform::Tool::Clippy),
+                        validator,
+                    );
+                }
             }
         }
     }
 }
 
-// run the following bash commands
-// ```bash
-// git checkout $commit_id
-// ```
-fn checkout(commit_id: git2::Oid) {
-    let repo = git2::Repository::open(".").unwrap();
-    let commit = repo.find_commit(commit_id);
-    repo.reset(commit.unwrap().as_object(),
-               git2::ResetType::Hard,
-               Some(git2::build::CheckoutBuilder::new()
-                .force()
-                .remove_untracked(true)),
-               ).ok();
+#[cfg(feature = "fix")]
+// print the changes of a file which a dry run would make
+fn preview(file: &str, input: &str, output: &str) {
+    if input == output {
+        return;
+    }
+    match suggestion::preview(file, input, output) {
+        Ok(diff) => print!("{diff}"),
+        Err(e) => println!("Cannot preview the changes of {file}: {e}"),
+    }
 }
 
-
 fn run(args: Args) {
+    if args.list_rules {
+        #[cfg(feature = "fix")]
+        match rules::Registry::new(args.rules.as_deref().map(Path::new)) {
+            Ok(registry) => registry.print_table(),
+            Err(e) => println!("Cannot load the rules: {e}"),
+        }
+        #[cfg(not(feature = "fix"))]
+        println!("To use the `--list-rules` option, please enable the `fix` feature");
+        return;
+    }
+    if let Some(dir) = &args.test_rules {
+        #[cfg(feature = "fix")]
+        match (
+            rules::Registry::new(args.rules.as_deref().map(Path::new)),
+            fixtures::fixtures(Path::new(dir)),
+        ) {
+            (Ok(registry), Ok(fixtures)) => {
+                let passed = fixtures::test_all(&fixtures, &registry);
+                println!("{passed} of {} fixtures passed", fixtures.len());
+            }
+            (Err(e), _) => println!("Cannot load the rules: {e}"),
+            (_, Err(e)) => println!("Cannot read the fixtures in {dir}: {e}"),
+        }
+        #[cfg(not(feature = "fix"))]
+        println!("To use the `--test-rules {dir}` option, please enable the `fix` feature");
+        return;
+    }
+    if let Some(batch) = &args.batch {
+        let output = args
+            .output
+            .clone()
+            .unwrap_or_else(|| "dataset.jsonl".to_string());
+        let summaries = batch::repositories(Path::new(batch)).and_then(|repos| {
+            batch::batch(
+                &repos,
+                &args.forwarded(),
+                args.mine.is_some(),
+                Path::new(&output),
+                std::time::Duration::from_secs(args.timeout),
+            )
+        });
+        match summaries {
+            Ok(summaries) => {
+                batch::print_summary(&summaries);
+                println!(
+                    "Succeeded on {} of {} repositories, with {} records in {output}.",
+                    summaries
+                        .iter()
+                        .filter(|s| s.status == batch::Status::Succeeded)
+                        .count(),
+                    summaries.len(),
+                    summaries.iter().map(|s| s.records).sum::<usize>()
+                );
+            }
+            Err(e) => println!("Cannot run the batch {batch}: {e}"),
+        }
+        return;
+    }
+    // without `--from`, the work tree is diagnosed in place and compared as if it were HEAD
+    let to = args.patch.clone().or_else(|| args.to.clone());
+    #[cfg(feature = "patch")]
+    if to.is_some() && args.from.is_none() && args.confirm {
+        if let Ok(repo) = git2::Repository::open(".") {
+            if revision::is_dirty(&repo) {
+                println!("The work tree has uncommitted changes, please commit or stash them before using `--confirm`");
+                return;
+            }
+        }
+    }
     remove_previously_generated_files("./diagnostics", "*.rs"); // marked up
-    #[cfg(fix)]
+    #[cfg(feature = "fix")]
     {
         remove_previously_generated_files("./original", "*.rs"); // before fix
         remove_previously_generated_files(".", "*.2.rs"); // transformed from
         remove_previously_generated_files(".", "*.3.rs"); // transformed to
+        remove_previously_generated_files("./transform", "*.diff"); // transformed as a diff
     }
     let mut flags = args.flags;
     if flags.is_empty() {
@@ -482,33 +814,120 @@ fn run(args: Args) {
             "disallowed_types".to_string(),
         ];
     }
-    let mut all_warnings = diagnose_all_warnings(flags.clone());
+    if let Some(range) = &args.mine {
+        #[cfg(feature = "patch")]
+        {
+            let output = args.output.unwrap_or_else(|| "fixes.jsonl".to_string());
+            match git2::Repository::open(".").and_then(|repo| {
+                mine::mine(
+                    &repo,
+                    range,
+                    &flags,
+                    args.retry_check,
+                    (args.errors, args.include_suppressed),
+                    Path::new(&output),
+                )
+            }) {
+                Ok(mined) => {
+                    println!(
+                        "Mined {} fixes from {} commits into {output}.",
+                        mined.fixes, mined.commits
+                    );
+                    if mined.unanalysable > 0 {
+                        println!("{} commits could not be analysed, because they or their parents do not build.", mined.unanalysable);
+                    }
+                }
+                Err(e) => println!("Cannot mine the commits {range}: {e}"),
+            }
+        }
+        #[cfg(not(feature = "patch"))]
+        {
+            println!("To use the `--mine {range}` option, please enable the `patch` feature");
+        }
+        return;
+    }
+    let (all_warnings, build) = match &args.from {
+        #[cfg(feature = "patch")]
+        Some(spec) => match git2::Repository::open(".").and_then(|repo| {
+            let r = revision::Revision::parse(&repo, spec)?;
+            Ok(revision::Snapshot::export(&repo, &r)?.diagnose(flags.clone(), args.retry_check))
+        }) {
+            Ok(warnings) => warnings,
+            Err(e) => {
+                println!("Cannot diagnose the revision {spec}: {e}");
+                return;
+            }
+        },
+        _ => diagnose_all_warnings(flags.clone(), Path::new("."), None, None, args.retry_check),
+    };
     let mut count = 0;
     all_warnings.iter().for_each(|(_k, v)| {
         count += v.len();
     });
-    println!(
-        "There are {} warnings in {} files.",
-        count,
-        all_warnings.len()
-    );
-    let patch = args.patch;
-    if patch.is_some() {
+    // keep the output of `--classify json` and `--provenance json` machine readable
+    if args.classify.as_deref() != Some("json") && args.provenance.as_deref() != Some("json") {
+        println!(
+            "There are {} warnings in {} files.",
+            count,
+            all_warnings.len()
+        );
+    }
+    if let Some(format) = &args.provenance {
         #[cfg(feature = "patch")]
+        match git2::Repository::open(".")
+            .and_then(|repo| provenance::provenance(&repo, &all_warnings))
         {
-            if let Some(id) = patch {
-                let repo = git2::Repository::open(".").unwrap();
-                let old_id = repo.head().unwrap().target().unwrap();
-                let c1 = repo
-                    .find_commit(repo.head().unwrap().target().unwrap())
-                    .unwrap();
-                let c2 = repo.find_commit(git2::Oid::from_str(&id).unwrap()).unwrap();
-                let a = Some(c1.tree().unwrap());
-                let b = Some(c2.tree().unwrap());
-                let mut diffopts2 = git2::DiffOptions::new();
-                let diff = repo
-                    .diff_tree_to_tree(a.as_ref(), b.as_ref(), Some(&mut diffopts2))
-                    .unwrap();
+            Ok(report) => match format.as_str() {
+                "json" => provenance::print_json(&report),
+                _ => provenance::print_table(&report),
+            },
+            Err(e) => println!("Cannot blame the warnings: {e}"),
+        }
+        #[cfg(not(feature = "patch"))]
+        println!("To use the `--provenance {format}` option, please enable the `patch` feature");
+        return;
+    }
+    if (to.is_some() || args.from.is_some()) && !build.analysable() {
+        // the missing warnings of a revision that does not build would look fixed
+        println!(
+            "The revision {} does not build, so its warnings cannot be analysed",
+            args.from.as_deref().unwrap_or("of the work tree")
+        );
+        return;
+    }
+    // marked with the warnings fixed by the hunks of the diff
+    #[cfg(feature = "patch")]
+    let mut all_warnings = all_warnings;
+    if to.is_some() || args.from.is_some() {
+        #[cfg(feature = "patch")]
+        {
+            let quiet = args.confirm || args.classify.is_some();
+            let repo = match git2::Repository::open(".") {
+                Ok(repo) => repo,
+                Err(e) => {
+                    println!("Cannot open the repository to compare the revisions: {e}");
+                    return;
+                }
+            };
+            let from = args.from.unwrap_or_else(|| "HEAD".to_string());
+            let to = to.unwrap_or_else(|| "WORKTREE".to_string());
+            let revisions = revision::Revision::parse(&repo, &from)
+                .and_then(|r1| Ok((r1, revision::Revision::parse(&repo, &to)?)));
+            if let Ok((r1, r2)) = revisions {
+                let diff = match revision::diff(&repo, &r1, &r2) {
+                    Ok(diff) => diff,
+                    Err(e) => {
+                        println!("Cannot compare the revisions {from}..{to}: {e}");
+                        return;
+                    }
+                };
+                let renames = revision::renames(&diff);
+                let lines = revision::LineMap::new(&diff);
+                let suppressions = if args.include_suppressed {
+                    suppression::Suppressions::default()
+                } else {
+                    suppression::Suppressions::from_diff(&repo, &diff)
+                };
                 let mut prev_hunk = 0;
                 let mut related_warnings = std::collections::HashSet::new();
                 diff.print(git2::DiffFormat::Patch, |delta, hunk, line| {
@@ -518,6 +937,7 @@ fn run(args: Args) {
                         all_warnings.iter_mut().for_each(|(k, v)| {
                             v.iter_mut().for_each(|m| {
                                 if std::path::Path::new(k) == p
+                                    && suppressions.of(k, m).is_none()
                                     && usize::try_from(h.old_start()).unwrap() <= m.end_line
                                     && usize::try_from(h.old_start() + h.old_lines()).unwrap()
                                         >= m.start_line
@@ -531,13 +951,13 @@ fn run(args: Args) {
                         if overlap {
                             if prev_hunk == 0 || prev_hunk != h.old_start() {
                                 related_warnings.iter().for_each(|m| {
-                                    if ! args.confirm {
+                                    if !quiet {
                                         println!("{}", m.name);
                                     }
                                 });
                                 related_warnings = std::collections::HashSet::new();
                             }
-                            if ! args.confirm {
+                            if !quiet {
                                 match line.origin() {
                                     ' ' | '+' | '-' => print!("{}", line.origin()),
                                     _ => {}
@@ -555,19 +975,35 @@ fn run(args: Args) {
                     }
                 })
                 .ok();
-                if args.confirm {
-                    // We go through the 2nd pass, to output only those confirmed fixes
-                    let oid = git2::Oid::from_str(&id).unwrap();
-                    checkout(oid);
-                    let all_new_warnings = diagnose_all_warnings(flags);
+                // We go through the 2nd pass, to output only those confirmed fixes
+                let (all_new_warnings, new_build) = if quiet {
+                    match revision::Snapshot::export(&repo, &r2) {
+                        Ok(snapshot) => snapshot.diagnose(flags.clone(), args.retry_check),
+                        Err(e) => {
+                            println!("Cannot export the revision {to}: {e}");
+                            return;
+                        }
+                    }
+                } else {
+                    (HashMap::new(), Build::default())
+                };
+                if args.confirm && !new_build.analysable() {
+                    println!("The revision {to} does not build, so the fixes cannot be confirmed");
+                } else if args.confirm {
                     all_warnings.iter_mut().for_each(|(k1, v1)| {
                         v1.iter_mut().for_each(|m1| {
                             if m1.fixed {
                                 let mut confirmed = true;
                                 all_new_warnings.iter().for_each(|(k2, v2)| {
                                     v2.iter().for_each(|m2| {
-                                        if k1 == k2 && m1.start_line <= m2.end_line && m1.end_line >= m2.start_line {
-                                           confirmed = false;
+                                        // compare the lines of the revision of the patch
+                                        let (start_line, end_line) =
+                                            lines.span(k1, m1.start_line, m1.end_line);
+                                        if revision::renamed(&renames, k1) == k2.as_str()
+                                            && start_line <= m2.end_line
+                                            && end_line >= m2.start_line
+                                        {
+                                            confirmed = false;
                                         }
                                     });
                                 });
@@ -575,7 +1011,6 @@ fn run(args: Args) {
                             }
                         });
                     });
-                    checkout(old_id);
                     prev_hunk = 0;
                     related_warnings = std::collections::HashSet::new();
                     diff.print(git2::DiffFormat::Patch, |delta, hunk, line| {
@@ -584,7 +1019,8 @@ fn run(args: Args) {
                         if let Some(h) = hunk {
                             all_warnings.iter_mut().for_each(|(k, v)| {
                                 v.iter_mut().for_each(|m| {
-                                    if m.fixed && std::path::Path::new(k) == p
+                                    if m.fixed
+                                        && std::path::Path::new(k) == p
                                         && usize::try_from(h.old_start()).unwrap() <= m.end_line
                                         && usize::try_from(h.old_start() + h.old_lines()).unwrap()
                                             >= m.start_line
@@ -619,16 +1055,54 @@ fn run(args: Args) {
                     })
                     .ok();
                 }
+                if let Some(format) = &args.classify {
+                    let records = if new_build.analysable() {
+                        outcome::classify(&repo, &diff, &all_warnings, &all_new_warnings)
+                    } else {
+                        outcome::unanalysable(&all_warnings)
+                    };
+                    match format.as_str() {
+                        "json" => outcome::print_json(&records),
+                        _ => outcome::print_table(&records),
+                    }
+                }
+            } else if let Err(e) = revisions {
+                println!("Cannot resolve the revisions {from}..{to}: {e}");
             }
         }
         #[cfg(not(feature = "patch"))]
         {
-            println!("To use the `--patch` option, please enable the `patch` feature");
+            println!("To use the `--patch`, `--from` or `--to` options, please enable the `patch` feature");
         }
     }
 
-    #[cfg(fix)]
-    fix_warnings(flags, &all_warnings);
+    #[cfg(feature = "fix")]
+    match rules::Registry::new(args.rules.as_deref().map(Path::new)) {
+        Ok(registry) => fix_warnings(
+            flags,
+            &all_warnings,
+            args.test_fixes,
+            (args.atomic, &args.applicability, args.dry_run),
+            &registry,
+        ),
+        Err(e) => println!("Cannot load the rules: {e}"),
+    }
+    #[cfg(not(feature = "fix"))]
+    if args.test_fixes {
+        println!("To use the `--test-fixes` option, please enable the `fix` feature");
+    }
+    #[cfg(not(feature = "fix"))]
+    if args.atomic {
+        println!("To use the `--atomic` option, please enable the `fix` feature");
+    }
+    #[cfg(not(feature = "fix"))]
+    if args.dry_run {
+        println!("To use the `--dry-run` option, please enable the `fix` feature");
+    }
+    #[cfg(not(feature = "fix"))]
+    if args.rules.is_some() {
+        println!("To use the `--rules` option, please enable the `fix` feature");
+    }
 }
 
 // Run cargo clippy to generate warnings from "foo.rs" into temporary "foo.rs.1" files
@@ -637,121 +1111,248 @@ fn main() {
     run(args);
 }
 
-#[cfg(fix)]
-mod fix {
-    extern crate reqwest;
-    const URL: &str = "http://bertrust.s3.amazonaws.com/unwrap_used.txl";
-    #[cfg(fix)]
-    fn fix_unwrap_used(file: &str) {
-        if !std::path::Path::new("unwrap_used.txl").exists() {
-            if let Ok(resp) = reqwest::blocking::get(URL) {
-                if let Ok(bytes) = resp.bytes() {
-                    std::fs::write("unwrap_used.txl", bytes).ok();
-                }
-            }
+#[cfg(feature = "fix")]
+// rewrite the file, which is a scratch copy, by the rules of a lint, returning the tool that
+// has run them. The first native rule to rewrite the warnings takes precedence; otherwise the
+// TXL rules are run one after another. TXL is run from a cache folder of its own, where
+// `txl_rs` unpacks its distribution relative to the current directory, and where the rules are
+// written for it to read. TXL is never downloaded: the rules are skipped until it has been
+// installed there.
+fn fix_by_rules(file: &Path, warnings: &[Ran], rules: &[rules::Rule]) -> Option<transform::Tool> {
+    let source = read_to_string(file).ok()?;
+    for r in rules {
+        let output = match &r.transform {
+            rules::Transform::Native(native) => native(&source, warnings),
+            rules::Transform::Pattern(patterns) => pattern::rewrite(&source, warnings, patterns),
+            rules::Transform::Txl(_) => continue,
+        };
+        if output != source {
+            std::fs::write(file, output).ok()?;
+            return Some(transform::Tool::TreeSitter);
         }
-        let args = vec![
-            "-q".to_string(),
-            "-s".to_string(),
-            "3000".to_string(),
-            file.to_string(),
-            "unwrap_used.txl".to_string(),
-        ];
-        if let Ok(output) = txl_rs::txl(args) {
-            std::fs::write(file, output).ok();
-            if let Ok(command) = Command::new("rustfmt")
-                .args([file])
-                .stdout(Stdio::piped())
-                .spawn()
-            {
-                if let Ok(_output) = command.wait_with_output() {
-                    if let Ok(s) = std::fs::read_to_string(file) {
-                        println!("{s}");
-                    }
+    }
+    let txl: Vec<(&String, &String)> = rules
+        .iter()
+        .filter_map(|r| match &r.transform {
+            rules::Transform::Txl(text) => Some((&r.name, text)),
+            _ => None,
+        })
+        .collect();
+    if txl.is_empty() {
+        return None;
+    }
+    let cache = std::env::temp_dir().join("rust-diagnostics-txl");
+    std::fs::create_dir_all(&cache).ok()?;
+    if !rules::installed(&cache) {
+        // which only matters when there are no native rules to fall back on
+        if txl.len() == rules.len() {
+            println!(
+                "Cannot find TXL in {} to run the rules on {}",
+                cache.display(),
+                file.display()
+            );
+        }
+        return None;
+    }
+    for (name, text) in txl {
+        let rule = cache.join(name);
+        if std::fs::write(&rule, text).is_ok() {
+            fix_by_rule(file, &rule, &cache);
+        }
+    }
+    Some(transform::Tool::Txl)
+}
+
+#[cfg(feature = "fix")]
+fn fix_by_rule(file: &Path, rule: &Path, cache: &Path) {
+    let args = vec![
+        "-q".to_string(),
+        "-s".to_string(),
+        "3000".to_string(),
+        file.display().to_string(),
+        rule.display().to_string(),
+    ];
+    let Ok(cd) = std::env::current_dir() else {
+        return;
+    };
+    std::env::set_current_dir(cache).ok();
+    let transformed = txl_rs::txl(args);
+    std::env::set_current_dir(cd).ok();
+    if let Ok(output) = transformed {
+        std::fs::write(file, output).ok();
+        if let Ok(command) = Command::new("rustfmt")
+            .args([file])
+            .stdout(Stdio::piped())
+            .spawn()
+        {
+            if let Ok(_output) = command.wait_with_output() {
+                if let Ok(s) = std::fs::read_to_string(file) {
+                    println!("{s}");
                 }
             }
         }
     }
-    fn to_fix(
-        flag: &str,
-        file: &String,
-        warnings: Vec<Ran>,
-        fixed_warnings: Vec<Ran>,
-        remaining_warnings: Vec<Ran>,
-        input: &String,
-        output: &[u8],
-    ) {
-        let trans_name = PathBuf::from("transform")
-            .join(flag.replace("-Wclippy::", ""))
-            .join(file);
-        let input_markedup = &markup(input.as_bytes(), warnings);
-        let output_markedup = &markup(output, remaining_warnings);
-        if let Ok(orig_items) = splitup(input_markedup) {
-            if let Ok(output_items) = splitup(output_markedup) {
-                if let Some(t) = trans_name.parent() {
-                    let path = PathBuf::from(&file);
-                    if let Some(p) = path.file_stem() {
-                        let mut found = false;
-                        let mut offset = Wrapping(0);
-                        for k1 in orig_items.keys().sorted() {
-                            if let Some(v1) = orig_items.get(k1) {
-                                for k2 in output_items.keys().sorted() {
-                                    if let Some(v2) = output_items.get(k2) {
-                                        if (Wrapping(*k1) + offset) == Wrapping(*k2) && *v1 != *v2 {
-                                            let pp = t.join(p);
-                                            if !pp.exists() {
-                                                std::fs::create_dir_all(&pp).ok();
-                                            }
-                                            let trans_filename1 = pp.join(format!("{}.2.rs", &k1));
-                                            let trans_filename2 = pp.join(format!("{}.3.rs", &k1));
-                                            if let Ok(vv1) = std::str::from_utf8(v1) {
-                                                if let Ok(vv2) = std::str::from_utf8(v2) {
-                                                    if let Ok(markedrules) =
-                                                        String::from_utf8(markup_rules(
-                                                            Wrapping(*k1),
-                                                            Wrapping(*k1) + Wrapping(vv1.len()),
-                                                            fixed_warnings.to_vec(),
-                                                        ))
-                                                    {
-                                                        let _ = &trans_filename1;
-                                                        std::fs::write(
-                                                            &trans_filename1,
-                                                            format!("{}{}", markedrules, vv1),
-                                                        )
-                                                        .ok();
-                                                        std::fs::write(
-                                                            &trans_filename2,
-                                                            format!("{}{}", markedrules, vv2),
-                                                        )
-                                                        .ok();
-                                                        found = true;
-                                                        offset +=
-                                                            Wrapping(v2.len()) - Wrapping(v1.len());
-                                                    }
-                                                }
-                                            }
-                                            if !found && pp.exists() {
-                                                std::fs::remove_dir_all(&pp).ok();
-                                            }
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
+}
+#[cfg(feature = "fix")]
+// transform the items of a file, or only those of an `instance` of a warning, which are kept
+// apart by the byte offset of the warning
+fn to_fix(
+    (flag, instance): (&str, Option<usize>),
+    file: &String,
+    warnings: Vec<Ran>,
+    (fixed_warnings, remaining_warnings): (Vec<Ran>, Vec<Ran>),
+    (input, output): (&String, &[u8]),
+    tool: transform::Tool,
+    validator: &transform::Validator,
+) {
+    let trans_name = std::path::PathBuf::from("transform")
+        .join(flag.replace("-Wclippy::", ""))
+        .join(file);
+    let input_markedup = String::from_utf8_lossy(&markup(input.as_bytes(), warnings)).to_string();
+    let output_markedup =
+        String::from_utf8_lossy(&markup(output, remaining_warnings.clone())).to_string();
+    let output_text = String::from_utf8_lossy(output);
+    // the items of the sources are paired by their identities, and located in the marked up
+    // sources by them too
+    let items = (transform::items(input), transform::items(&output_text));
+    let marked_items = (
+        transform::items(&input_markedup),
+        transform::items(&output_markedup),
+    );
+    let (Some(t), Some(p)) = (trans_name.parent(), Path::new(file).file_stem()) else {
+        return;
+    };
+    let pp = match instance {
+        Some(start) => t.join(p).join(start.to_string()),
+        None => t.join(p),
+    };
+    for (before, after) in transform::pair(&items.0, &items.1) {
+        let marked_before =
+            before.and_then(|b| transform::counterpart(&items.0, &marked_items.0, b.start_byte));
+        let marked_after =
+            after.and_then(|a| transform::counterpart(&items.1, &marked_items.1, a.start_byte));
+        let diff_name = match (before, after, marked_before, marked_after) {
+            (Some(b), Some(a), Some(mb), Some(ma)) => {
+                if input[b.start_byte..b.end_byte] == output_text[a.start_byte..a.end_byte] {
+                    continue;
                 }
+                std::fs::create_dir_all(&pp).ok();
+                let markedrules = String::from_utf8_lossy(&markup_rules(
+                    Wrapping(b.start_byte),
+                    Wrapping(b.end_byte),
+                    fixed_warnings.to_vec(),
+                ))
+                .to_string();
+                std::fs::write(
+                    pp.join(format!("{}.2.rs", mb.start_byte)),
+                    format!(
+                        "{}{}",
+                        markedrules,
+                        &input_markedup[mb.start_byte..mb.end_byte]
+                    ),
+                )
+                .ok();
+                std::fs::write(
+                    pp.join(format!("{}.3.rs", mb.start_byte)),
+                    format!(
+                        "{}{}",
+                        markedrules,
+                        &output_markedup[ma.start_byte..ma.end_byte]
+                    ),
+                )
+                .ok();
+                pp.join(format!("{}.diff", mb.start_byte))
             }
+            (Some(_), None, Some(mb), _) => pp.join(format!("{}.removed.diff", mb.start_byte)),
+            (None, Some(_), _, Some(ma)) => pp.join(format!("{}.added.diff", ma.start_byte)),
+            _ => continue,
+        };
+        std::fs::create_dir_all(&pp).ok();
+        to_transformation(
+            (flag, file),
+            (before, after),
+            (&fixed_warnings, &remaining_warnings),
+            (input, output),
+            tool,
+            validator,
+            &diff_name,
+        );
+    }
+}
+
+#[cfg(feature = "fix")]
+const MANIFEST: &str = "transform/manifest.jsonl";
+
+#[cfg(feature = "fix")]
+// write the unified diff of the transformation of an item, validate it on its own, and record
+// it in the manifest
+fn to_transformation(
+    (flag, file): (&str, &str),
+    (before, after): (Option<&transform::Item>, Option<&transform::Item>),
+    (fixed_warnings, remaining_warnings): (&[Ran], &[Ran]),
+    (input, output): (&str, &[u8]),
+    tool: transform::Tool,
+    validator: &transform::Validator,
+    diff_name: &Path,
+) {
+    let (item, change) = match (before, after) {
+        (Some(b), Some(_)) => (b, transform::Change::Changed),
+        (Some(b), None) => (b, transform::Change::Removed),
+        (None, Some(a)) => (a, transform::Change::Added),
+        (None, None) => return,
+    };
+    let diff = transform::unified_diff(file, (input.as_bytes(), output), before, after);
+    let (diff, applied) = match diff {
+        Ok(diff) => diff,
+        Err(e) => {
+            println!("Cannot diff the transformation of {}: {e}", item.path());
+            return;
         }
+    };
+    std::fs::write(diff_name, diff).ok();
+    let validation = validator.validate(file, input.as_bytes(), &applied);
+    let record = transform::Record {
+        lint: flag.replace("-Wclippy::", "clippy::"),
+        file: file.to_string(),
+        item: item.path(),
+        kind: item.kind.clone(),
+        change,
+        fixed: before.map_or_else(Vec::new, |b| {
+            transform::Warning::within(fixed_warnings, b.start_line, b.end_line)
+        }),
+        remaining: after.map_or_else(Vec::new, |a| {
+            transform::Warning::within(remaining_warnings, a.start_line, a.end_line)
+        }),
+        tool,
+        diff: diff_name.display().to_string(),
+        validation,
+    };
+    if let Err(e) = transform::append(Path::new(MANIFEST), &record) {
+        println!("Cannot record the transformation of {}: {e}", item.path());
     }
 }
 
+// the highlighted parts of the lines of a span
+fn highlighted(lines: &[DiagnosticSpanLine]) -> String {
+    lines
+        .iter()
+        .map(|l| {
+            l.text
+                .chars()
+                .skip(l.highlight_start.saturating_sub(1))
+                .take(l.highlight_end.saturating_sub(l.highlight_start))
+                .collect::<String>()
+        })
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
 fn sub_messages(children: &[Diagnostic]) -> String {
     children
         .iter()
         .map(|x| {
             if let Some(rendered) = &x.rendered {
-                format!("{}: {}", &x.message, &rendered)
+                format!("{}: {}", x.message, rendered)
             } else {
                 x.message.to_owned()
             }
@@ -760,6 +1361,30 @@ fn sub_messages(children: &[Diagnostic]) -> String {
         .join("\n")
 }
 
+// the replacements suggested by the spans of the children, and of their own children
+fn sub_suggestions(children: &[Diagnostic]) -> Vec<Suggested> {
+    let mut suggestions = Vec::new();
+    for x in children {
+        for s in &x.spans {
+            if let Some(replacement) = &s.suggested_replacement {
+                suggestions.push(Suggested {
+                    message: x.message.clone(),
+                    file: s.file_name.clone(),
+                    start_line: s.line_start,
+                    end_line: s.line_end,
+                    replacement: replacement.clone(),
+                    applicability: s
+                        .suggestion_applicability
+                        .as_ref()
+                        .map(|a| format!("{a:?}")),
+                });
+            }
+        }
+        suggestions.extend(sub_suggestions(&x.children));
+    }
+    suggestions
+}
+
 // remove the previously generated files under folder, matching with the pattern
 fn remove_previously_generated_files(folder: &str, pattern: &str) {
     if !std::path::Path::new(folder).exists() {
@@ -795,8 +1420,29 @@ fn remove_previously_generated_files(folder: &str, pattern: &str) {
 
 #[cfg(test)]
 mod tests {
-    use serial_test::serial;
     use super::*;
+    use serial_test::serial;
+    use std::sync::Arc;
+
+    // a warning of the lint on the first occurrence of the code in the source
+    #[cfg(feature = "fix")]
+    fn flagged(source: &str, code: &str, lint: &str) -> Ran {
+        let start = source.find(code).unwrap();
+        Ran {
+            name: format!("#[Warning(clippy::{lint})"),
+            start,
+            end: start + code.len(),
+            suggestion: "None".to_string(),
+            note: String::new(),
+            suggestions: Vec::new(),
+            code: code.to_string(),
+            rendered: String::new(),
+            explanation: String::new(),
+            start_line: 0,
+            end_line: 0,
+            fixed: false,
+        }
+    }
     #[test]
     #[serial]
     fn diagnostics() {
@@ -804,12 +1450,16 @@ mod tests {
             flags: vec![],
             patch: None,
             confirm: false,
+            ..Default::default()
         };
         let dir = std::path::Path::new("abc");
         if dir.exists() {
             let _ = std::fs::remove_dir_all(dir);
         }
-        if let Ok(command) = Command::new("cargo").args(["init", "--bin", "--vcs", "git", "abc"]).spawn() {
+        if let Ok(command) = Command::new("cargo")
+            .args(["init", "--bin", "--vcs", "git", "abc"])
+            .spawn()
+        {
             if let Ok(_output) = command.wait_with_output() {
                 let code = r#"
 fn main() {
@@ -823,7 +1473,9 @@ fn main() {
                 run(args);
                 assert!(std::path::Path::new("diagnostics/src/main.rs").exists());
                 if let Ok(s) = std::fs::read_to_string("diagnostics/src/main.rs") {
-                    assert_eq! (s, r###"
+                    assert_eq!(
+                        s,
+                        r###"
 fn main() {
     let s = /*#[Warning(clippy::unwrap_used)*/std::fs::read_to_string("Cargo.toml").unwrap()/*
 #[Warning(clippy::unwrap_used)
@@ -832,7 +1484,8 @@ for further information visit https://rust-lang.github.io/rust-clippy/master/ind
 requested on the command line with `-W clippy::unwrap-used`*/;
     println!("{s}");
 }
-"###);
+"###
+                    );
                 }
                 std::env::set_current_dir(cd).ok();
             }
@@ -851,6 +1504,7 @@ requested on the command line with `-W clippy::unwrap-used`*/;
         let author = git2::Signature::now("Yijun Yu", "y.yu@open.ac.uk").unwrap();
         let mut index = repo.index().unwrap();
         index.add_path(std::path::Path::new(filename)).ok();
+        index.write().ok();
         let index_oid = index.write_tree_to(&repo).unwrap();
         let tree = repo.find_tree(index_oid).unwrap();
         let h = repo.head();
@@ -860,37 +1514,71 @@ requested on the command line with `-W clippy::unwrap-used`*/;
             let result = repo.commit(Some("HEAD"), &author, &author, message, &tree, &[&parent]);
             result.and_then(|oid| {
                 repo.find_object(oid, None).and_then(|object| {
-                    repo.reset(&object, git2::ResetType::Soft, None).map(|_| oid)
+                    repo.reset(&object, git2::ResetType::Soft, None)
+                        .map(|_| oid)
                 })
             })
         } else {
             let result = repo.commit(Some("HEAD"), &author, &author, message, &tree, &[]);
             result.and_then(|oid| {
                 repo.find_object(oid, None).and_then(|object| {
-                    repo.reset(&object, git2::ResetType::Soft, None).map(|_| oid)
+                    repo.reset(&object, git2::ResetType::Soft, None)
+                        .map(|_| oid)
                 })
             })
         }
     }
 
+    // run the following bash commands
+    // ```bash
+    // git checkout $commit_id
+    // ```
+    fn checkout(commit_id: git2::Oid) {
+        let repo = git2::Repository::open(".").unwrap();
+        let commit = repo.find_commit(commit_id);
+        repo.reset(
+            commit.unwrap().as_object(),
+            git2::ResetType::Hard,
+            Some(
+                git2::build::CheckoutBuilder::new()
+                    .force()
+                    .remove_untracked(true),
+            ),
+        )
+        .ok();
+    }
+
     fn setup(code: &str, fix: &str) -> Result<(std::path::PathBuf, git2::Oid), std::io::Error> {
         let dir = std::path::Path::new("abc");
         if dir.exists() {
             let _ = std::fs::remove_dir_all(dir);
         }
-        if let Ok(command) = Command::new("cargo").args(["init", "--vcs", "git", "--bin", "abc"]).spawn() {
+        if let Ok(command) = Command::new("cargo")
+            .args(["init", "--vcs", "git", "--bin", "abc"])
+            .spawn()
+        {
             if let Ok(_output) = command.wait_with_output() {
                 let cd = std::env::current_dir().unwrap();
                 std::env::set_current_dir(dir).ok();
+                let manifest = std::fs::read_to_string("Cargo.toml").unwrap();
+                commit_file("manifest", "Cargo.toml", &manifest)
+                    .ok()
+                    .unwrap();
                 let init_commit = commit_file("init", "src/main.rs", code).ok().unwrap();
                 let update_commit = commit_file("update", "src/main.rs", fix).ok().unwrap();
                 checkout(init_commit);
                 Ok((cd, update_commit))
             } else {
-                Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Cannot initiate the cargo project"))
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::NotFound,
+                    "Cannot initiate the cargo project",
+                ))
             }
         } else {
-            Err(std::io::Error::new(std::io::ErrorKind::NotFound, "Cannot checkout"))
+            Err(std::io::Error::new(
+                std::io::ErrorKind::NotFound,
+                "Cannot checkout",
+            ))
         }
     }
 
@@ -918,24 +1606,27 @@ requested on the command line with `-W clippy::unwrap-used`*/;
     // cd -
     // ```
     fn fixed() {
-       if let Ok((cd, update_commit)) = setup(r#"
+        if let Ok((cd, update_commit)) = setup(
+            r#"
 fn main() {
     let s = std::fs::read_to_string("Cargo.toml").unwrap();
     println!("{s}");
 }
-"#,r#"
+"#,
+            r#"
 fn main() {
     if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
         println!("{s}");
     }
 }
-"#) 
-        {
+"#,
+        ) {
             let debug_confirm = true;
             let args = Args {
                 flags: vec![],
                 patch: Some(format!("{update_commit}")),
                 confirm: debug_confirm,
+                ..Default::default()
             };
             std::io::set_output_capture(Some(Default::default()));
             run(args);
@@ -943,7 +1634,9 @@ fn main() {
             let captured = Arc::try_unwrap(captured).unwrap();
             let captured = captured.into_inner().unwrap();
             let captured = String::from_utf8(captured).unwrap();
-            assert_eq!(captured, r###"There are 1 warnings in 1 files.
+            assert_eq!(
+                captured,
+                r###"There are 1 warnings in 1 files.
 #[Warning(clippy::unwrap_used)
 @@ -1,5 +1,6 @@
  
@@ -954,30 +1647,1700 @@ fn main() {
 +        println!("{s}");
 +    }
  }
-"###);
+"###
+            );
             teardown(cd, update_commit);
         }
     }
 
+    #[cfg(feature = "fix")]
+    // the files of the work tree, apart from those generated by cargo, git or the tool itself
+    fn snapshot(dir: &Path, files: &mut std::collections::BTreeMap<std::path::PathBuf, Vec<u8>>) {
+        for entry in std::fs::read_dir(dir).unwrap().flatten() {
+            let path = entry.path();
+            let name = entry.file_name();
+            if ["target", "Cargo.lock", ".git", "diagnostics", "transform"]
+                .iter()
+                .any(|s| name == *s)
+            {
+                continue;
+            }
+            if path.is_dir() {
+                snapshot(&path, files);
+            } else {
+                files.insert(path.clone(), std::fs::read(&path).unwrap());
+            }
+        }
+    }
+
+    #[cfg(feature = "fix")]
     #[test]
     #[serial]
-    fn unfixed() {
-       if let Ok((cd, update_commit)) = setup(r#"
+    fn scratch() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
 fn main() {
-    let s = std::fs::read_to_string("Cargo.toml").unwrap();
-    println!("{s}");
+    println!("{}", answer());
 }
-"#,r#"
+
+fn answer() -> i32 {
+    return 42;
+}
+"#,
+            r#"
 fn main() {
-    let s = std::fs::read_to_string("Cargo.toml").unwrap();
-    println!("The configuration file is: {s}");
+    println!("{}", answer());
 }
-"#) 
-        {
+
+fn answer() -> i32 {
+    42
+}
+"#,
+        ) {
+            let mut before = std::collections::BTreeMap::new();
+            snapshot(Path::new("."), &mut before);
             let args = Args {
-                flags: vec![],
-                patch: Some(format!("{update_commit}")),
-                confirm: true,
+                flags: vec!["needless_return".to_string()],
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            std::io::set_output_capture(None);
+            let mut after = std::collections::BTreeMap::new();
+            snapshot(Path::new("."), &mut after);
+            assert_eq!(before, after);
+            let fixed = std::fs::read_to_string("transform/needless_return/src/main/46.3.rs");
+            teardown(cd, update_commit);
+            assert_eq!(
+                fixed.unwrap(),
+                r#"/*#[Warning(clippy::needless_return)*/
+fn answer() -> i32 {
+    42
+}"#
+            );
+        }
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn manifest() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+struct Foo;
+
+impl Foo {
+    fn answer(&self) -> i32 {
+        return 42;
+    }
+}
+
+fn main() {
+    println!("{}", Foo.answer());
+}
+"#,
+            r#"
+struct Foo;
+
+impl Foo {
+    fn answer(&self) -> i32 {
+        42
+    }
+}
+
+fn main() {
+    println!("{}", Foo.answer());
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec!["needless_return".to_string()],
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            std::io::set_output_capture(None);
+            let manifest = std::fs::read_to_string("transform/manifest.jsonl");
+            let diff = std::fs::read_to_string("transform/needless_return/src/main/14.diff");
+            teardown(cd, update_commit);
+            assert_eq!(
+                manifest.unwrap(),
+                r#"{"lint":"clippy::needless_return","file":"src/main.rs","item":"impl Foo","kind":"impl_item","change":"changed","fixed":[{"lint":"clippy::needless_return","start_line":6,"end_line":6}],"remaining":[],"tool":"clippy","diff":"transform/needless_return/src/main/14.diff","validation":"compiles"}
+{"lint":"clippy::needless_return","file":"src/main.rs","item":"impl Foo::answer","kind":"function_item","change":"changed","fixed":[{"lint":"clippy::needless_return","start_line":6,"end_line":6}],"remaining":[],"tool":"clippy","diff":"transform/needless_return/src/main/29.diff","validation":"compiles"}
+"#
+            );
+            assert_eq!(
+                diff.unwrap(),
+                concat!(
+                    "--- a/src/main.rs\n",
+                    "+++ b/src/main.rs\n",
+                    "@@ -3,7 +3,7 @@ struct Foo;\n",
+                    " \n",
+                    " impl Foo {\n",
+                    "     fn answer(&self) -> i32 {\n",
+                    "-        return 42;\n",
+                    "+        42\n",
+                    "     }\n",
+                    " }\n",
+                    " \n",
+                )
+            );
+        }
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn validated() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn answer() -> i32 {
+    return 42;
+}
+
+fn main() {
+    println!("{}", answer());
+}
+"#,
+            r#"
+fn answer() -> i32 {
+    42
+}
+
+fn main() {
+    println!("{}", answer());
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec!["needless_return".to_string()],
+                test_fixes: true,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            std::io::set_output_capture(None);
+            let manifest = std::fs::read_to_string("transform/manifest.jsonl").unwrap();
+            let original = std::fs::read("src/main.rs").unwrap();
+            let validator = transform::Validator::new(Path::new("."), true);
+            let broken = validator.validate("src/main.rs", &original, b"fn main() -> i32 {}");
+            let restored = std::fs::read("src/main.rs").unwrap();
+            teardown(cd, update_commit);
+            assert!(manifest.ends_with("\"validation\":\"tests-pass\"}\n"));
+            assert_eq!(broken, transform::Validation::Regressed);
+            assert_eq!(restored, original);
+        }
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn atomic() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn one() -> i32 {
+    return 1;
+}
+
+fn two() -> i32 {
+    return 2;
+}
+
+fn main() {
+    println!("{}", one() + two());
+}
+"#,
+            r#"
+fn one() -> i32 {
+    1
+}
+
+fn two() -> i32 {
+    2
+}
+
+fn main() {
+    println!("{}", one() + two());
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec!["needless_return".to_string()],
+                atomic: true,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            std::io::set_output_capture(None);
+            let manifest = std::fs::read_to_string("transform/manifest.jsonl");
+            let fixed = std::fs::read_to_string("transform/needless_return/src/main/58/36.3.rs");
+            teardown(cd, update_commit);
+            // each warning is fixed apart from the other, in the item it is in
+            assert_eq!(
+                manifest.unwrap(),
+                r#"{"lint":"clippy::needless_return","file":"src/main.rs","item":"one","kind":"function_item","change":"changed","fixed":[{"lint":"clippy::needless_return","start_line":3,"end_line":3}],"remaining":[],"tool":"clippy","diff":"transform/needless_return/src/main/23/1.diff","validation":"compiles"}
+{"lint":"clippy::needless_return","file":"src/main.rs","item":"two","kind":"function_item","change":"changed","fixed":[{"lint":"clippy::needless_return","start_line":7,"end_line":7}],"remaining":[],"tool":"clippy","diff":"transform/needless_return/src/main/58/36.diff","validation":"compiles"}
+"#
+            );
+            assert_eq!(
+                fixed.unwrap(),
+                r#"/*#[Warning(clippy::needless_return)*/
+fn two() -> i32 {
+    2
+}"#
+            );
+        }
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    fn suggestions() {
+        let source = "fn main() { let x = vec![1]; println!(\"{}\", x.len() == 0); }";
+        let suggestion =
+            |lint: &str, applicability, edits: &[(usize, usize, &str)]| suggestion::Suggestion {
+                lint: lint.to_string(),
+                file: "src/main.rs".to_string(),
+                start: edits[0].0,
+                end: edits[0].1,
+                line: 1,
+                message: String::new(),
+                applicability,
+                edits: edits
+                    .iter()
+                    .map(|(start, end, replacement)| suggestion::Edit {
+                        file: "src/main.rs".to_string(),
+                        start: *start,
+                        end: *end,
+                        replacement: replacement.to_string(),
+                    })
+                    .collect(),
+            };
+        let suggestions = [
+            // a multipart suggestion is applied as a whole
+            suggestion("clippy::useless_vec", 0, &[(20, 25, "["), (26, 27, "]")]),
+            suggestion("clippy::len_zero", 0, &[(44, 56, "x.is_empty()")]),
+            // which an overlapping suggestion cannot change
+            suggestion("clippy::other", 0, &[(44, 45, "y")]),
+            suggestion("clippy::unsafe", 1, &[(4, 8, "start")]),
+        ];
+        let threshold = suggestion::threshold("machine-applicable");
+        let plan = suggestion::plan("src/main.rs", suggestions.iter(), threshold);
+        let lints = |v: &[&suggestion::Suggestion]| {
+            v.iter().map(|s| s.lint.clone()).collect::<Vec<String>>()
+        };
+        assert_eq!(
+            lints(&plan.accepted),
+            vec!["clippy::useless_vec", "clippy::len_zero"]
+        );
+        assert_eq!(lints(&plan.conflicts), vec!["clippy::other"]);
+        assert_eq!(
+            suggestion::apply("src/main.rs", source, &plan.accepted),
+            "fn main() { let x = [1]; println!(\"{}\", x.is_empty()); }"
+        );
+        let threshold = suggestion::threshold("maybe-incorrect");
+        let plan = suggestion::plan("src/main.rs", suggestions.iter(), threshold);
+        assert_eq!(
+            lints(&plan.accepted),
+            vec!["clippy::useless_vec", "clippy::len_zero", "clippy::unsafe"]
+        );
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn dry_run() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn answer() -> i32 {
+    return 42;
+}
+
+fn main() {
+    println!("{}", answer());
+}
+"#,
+            r#"
+fn answer() -> i32 {
+    42
+}
+
+fn main() {
+    println!("{}", answer());
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec!["needless_return".to_string()],
+                dry_run: true,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            let transformed = Path::new("transform").exists();
+            teardown(cd, update_commit);
+            assert_eq!(
+                captured,
+                concat!(
+                    "There are 1 warnings in 1 files.\n",
+                    "diff --git a/src/main.rs b/src/main.rs\n",
+                    "index 0195683..af054e6 100644\n",
+                    "--- a/src/main.rs\n",
+                    "+++ b/src/main.rs\n",
+                    "@@ -1,6 +1,6 @@\n",
+                    " \n",
+                    " fn answer() -> i32 {\n",
+                    "-    return 42;\n",
+                    "+    42\n",
+                    " }\n",
+                    " \n",
+                    " fn main() {\n",
+                )
+            );
+            assert!(!transformed);
+        }
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    fn rewrite() {
+        let source = r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+
+fn config() -> std::io::Result<String> {
+    let s = std::fs::read_to_string("Cargo.lock").unwrap();
+    Ok(s)
+}
+
+fn first(v: &[i32]) -> Option<i32> {
+    let x = v.first().unwrap();
+    Some(*x)
+}
+
+fn print(w: &[i32]) {
+    let x = w.first().unwrap();
+    println!("{x}")
+}
+"#;
+        let warning = |call: &str, note: &str| Ran {
+            note: note.to_string(),
+            ..flagged(source, call, "unwrap_used")
+        };
+        let err = "if this value is an `Err`, it will panic";
+        let none = "if this value is `None`, it will panic";
+        let warnings = [
+            warning(r#"std::fs::read_to_string("Cargo.toml").unwrap()"#, err),
+            warning(r#"std::fs::read_to_string("Cargo.lock").unwrap()"#, err),
+            warning("v.first().unwrap()", none),
+            warning("w.first().unwrap()", none),
+        ];
+        assert_eq!(
+            rewrite::unwrap_used(source, &warnings),
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+
+fn config() -> std::io::Result<String> {
+    let s = std::fs::read_to_string("Cargo.lock")?;
+    Ok(s)
+}
+
+fn first(v: &[i32]) -> Option<i32> {
+    let x = v.first()?;
+    Some(*x)
+}
+
+fn print(w: &[i32]) {
+    let Some(x) = w.first() else {
+        return;
+    };
+    println!("{x}")
+}
+"#
+        );
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    fn casts() {
+        let source = r#"
+fn widen(x: u8) -> u64 {
+    x as u64
+}
+
+fn narrow(x: u64) -> Result<u32, std::num::TryFromIntError> {
+    let y = (x / 2) as u32;
+    Ok(y)
+}
+
+fn round(f: f64) -> i32 {
+    f as i32
+}
+
+fn scale(n: i32) -> f32 {
+    n as f32
+}
+
+fn main() {
+    let x: i64 = -1;
+    let y = x as u8;
+    println!("{y}");
+}
+"#;
+        let warning = |source: &str, cast: &str| flagged(source, cast, "cast_possible_truncation");
+        let truncated = |source: &str, cast: &str, from: &str, to: &str| Ran {
+            rendered: format!("warning: casting `{from}` to `{to}` may truncate the value"),
+            ..warning(source, cast)
+        };
+        let output = rewrite::cast_lossless(source, &[warning(source, "x as u64")]);
+        // the type of `x` is the declared one, since the warning does not tell it
+        let output = rewrite::fallible_casts(&output, &[warning(&output, "x as u8")]);
+        let output = rewrite::fallible_casts(
+            &output,
+            &[truncated(&output, "(x / 2) as u32", "u64", "u32")],
+        );
+        // there is no `TryFrom` between floats and integers
+        let output =
+            rewrite::fallible_casts(&output, &[truncated(&output, "f as i32", "f64", "i32")]);
+        let output = rewrite::fallible_casts(&output, &[warning(&output, "n as f32")]);
+        assert_eq!(
+            output,
+            r#"
+fn widen(x: u8) -> u64 {
+    u64::from(x)
+}
+
+fn narrow(x: u64) -> Result<u32, std::num::TryFromIntError> {
+    let y = u32::try_from(x / 2)?;
+    Ok(y)
+}
+
+fn round(f: f64) -> i32 {
+    f as i32
+}
+
+fn scale(n: i32) -> f32 {
+    n as f32
+}
+
+fn main() {
+    let x: i64 = -1;
+    if let Ok(y) = u8::try_from(x) {
+        println!("{y}");
+    }
+}
+"#
+        );
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    fn expect_dbg_float_cmp() {
+        let source = r#"
+fn home() -> Result<String, String> {
+    let home = std::env::var("HOME").expect("HOME is not set");
+    Ok(home)
+}
+
+fn main() {
+    let user = std::env::var("USER").expect("USER is not set");
+    let a = 0.1_f64 + 0.2;
+    let b = dbg!(a * 2.0);
+    let c = dbg!(b);
+    dbg!(&user);
+    println!("{}", a == 0.3);
+    println!("{}", c != 0.6);
+}
+"#;
+        let warning = |source: &str, lint: &str, code: &str| Ran {
+            note: "if this value is an `Err`, it will panic".to_string(),
+            ..flagged(source, code, lint)
+        };
+        let mut output = source.to_string();
+        for (lint, code, native) in [
+            (
+                "expect_used",
+                r#"std::env::var("HOME").expect("HOME is not set")"#,
+                rewrite::expect_used as rules::Native,
+            ),
+            (
+                "expect_used",
+                r#"std::env::var("USER").expect("USER is not set")"#,
+                rewrite::expect_used,
+            ),
+            ("dbg_macro", "dbg!(a * 2.0)", rewrite::dbg_macro),
+            ("dbg_macro", "dbg!(b)", rewrite::dbg_macro),
+            ("dbg_macro", "dbg!(&user)", rewrite::dbg_macro),
+            ("float_cmp", "a == 0.3", rewrite::float_cmp),
+            ("float_cmp", "c != 0.6", rewrite::float_cmp),
+        ] {
+            output = native(&output, &[warning(&output, lint, code)]);
+        }
+        assert_eq!(
+            output,
+            r#"
+fn home() -> Result<String, String> {
+    let home = std::env::var("HOME").map_err(|e| format!("HOME is not set: {e}"))?;
+    Ok(home)
+}
+
+fn main() {
+    if let Ok(user) = std::env::var("USER") {
+        let a = 0.1_f64 + 0.2;
+        let b = a * 2.0;
+        let c = b;
+        println!("{}", (a - 0.3).abs() < f64::EPSILON);
+        println!("{}", (c - 0.6).abs() >= f64::EPSILON);
+    } else {
+        eprintln!("USER is not set");
+    }
+}
+"#
+        );
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn float_cmp_rules() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let a = 0.1_f64 + 0.2;
+    println!("{}", a == 0.3);
+}
+"#,
+            r#"
+fn main() {
+    let a = 0.1_f64 + 0.2;
+    println!("{}", (a - 0.3).abs() < f64::EPSILON);
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec!["float_cmp".to_string()],
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            std::io::set_output_capture(None);
+            let manifest = std::fs::read_to_string("transform/manifest.jsonl");
+            teardown(cd, update_commit);
+            assert_eq!(
+                manifest.unwrap(),
+                r#"{"lint":"clippy::float_cmp","file":"src/main.rs","item":"main","kind":"function_item","change":"changed","fixed":[{"lint":"clippy::float_cmp","start_line":4,"end_line":4}],"remaining":[],"tool":"tree-sitter","diff":"transform/float_cmp/src/main/1.diff","validation":"compiles"}
+{"lint":"clippy::float_cmp","file":"src/main.rs","item":"main::println!","kind":"macro_invocation","change":"changed","fixed":[{"lint":"clippy::float_cmp","start_line":4,"end_line":4}],"remaining":[],"tool":"tree-sitter","diff":"transform/float_cmp/src/main/44.diff","validation":"compiles"}
+"#
+            );
+        }
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn cast_rules() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let x: u64 = 42;
+    let y = x as u32;
+    println!("{y}");
+}
+"#,
+            r#"
+fn main() {
+    let x: u64 = 42;
+    if let Ok(y) = u32::try_from(x) {
+        println!("{y}");
+    }
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec!["cast_possible_truncation".to_string()],
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            std::io::set_output_capture(None);
+            let manifest = std::fs::read_to_string("transform/manifest.jsonl");
+            teardown(cd, update_commit);
+            assert_eq!(
+                manifest.unwrap(),
+                r#"{"lint":"clippy::cast_possible_truncation","file":"src/main.rs","item":"main","kind":"function_item","change":"changed","fixed":[{"lint":"clippy::cast_possible_truncation","start_line":4,"end_line":4}],"remaining":[],"tool":"tree-sitter","diff":"transform/cast_possible_truncation/src/main/1.diff","validation":"compiles"}
+{"lint":"clippy::cast_possible_truncation","file":"src/main.rs","item":"main::let y","kind":"let_declaration","change":"removed","fixed":[{"lint":"clippy::cast_possible_truncation","start_line":4,"end_line":4}],"remaining":[],"tool":"tree-sitter","diff":"transform/cast_possible_truncation/src/main/38.removed.diff","validation":"compiles"}
+"#
+            );
+        }
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    fn patterns() {
+        let source = r#"
+fn config() -> std::io::Result<String> {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    Ok(s)
+}
+
+fn first(v: &[i32]) -> Option<i32> {
+    let x = v.first().unwrap();
+    Some(x.max(&0) + x.min(&0))
+}
+"#;
+        let warning = |call: &str| flagged(source, call, "unwrap_used");
+        let warnings = [
+            warning(r#"std::fs::read_to_string("Cargo.toml").unwrap()"#),
+            warning("v.first().unwrap()"),
+            warning("x.max(&0) + x.min(&0)"),
+        ];
+        let patterns = pattern::parse(
+            r#"
+# the error is propagated only where the function returns a `Result`
+$e.unwrap() ==>> $e? where returns Result
+$a.max($b) + $a.min($b) ==>> $a + $b
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            patterns[0],
+            pattern::Pattern {
+                search: "$e.unwrap()".to_string(),
+                replace: "$e?".to_string(),
+                returns: Some("Result".to_string()),
+            }
+        );
+        assert_eq!(
+            pattern::rewrite(source, &warnings, &patterns),
+            r#"
+fn config() -> std::io::Result<String> {
+    let s = std::fs::read_to_string("Cargo.toml")?;
+    Ok(s)
+}
+
+fn first(v: &[i32]) -> Option<i32> {
+    let x = v.first().unwrap();
+    Some(x + &0)
+}
+"#
+        );
+        assert_eq!(
+            pattern::parse("$e.unwrap()").unwrap_err(),
+            "line 1: expected `<search> ==>> <replace>`"
+        );
+        assert_eq!(
+            pattern::parse("\n$e.unwrap( ==>> $e?").unwrap_err(),
+            "line 2: cannot parse `$e.unwrap(` as an expression"
+        );
+        assert_eq!(
+            pattern::parse("$e.unwrap() ==>> $f?").unwrap_err(),
+            "line 1: $f is not bound by `$e.unwrap()`"
+        );
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn rules() {
+        let dir =
+            std::env::temp_dir().join(format!("rust-diagnostics-rules-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).ok();
+        std::fs::write(dir.join("unwrap_used.txl"), "% replaces the bundled rule").ok();
+        std::fs::write(
+            dir.join("unwrap_used.ssr"),
+            "$e.unwrap() ==>> $e.unwrap_or_default()",
+        )
+        .ok();
+        std::fs::write(
+            dir.join("expect_used.if_let.txl"),
+            "% a rule of another lint",
+        )
+        .ok();
+        std::fs::write(dir.join("README.md"), "not a rule").ok();
+        let registry = rules::Registry::new(Some(&dir)).unwrap();
+        // the local rules replace the native and bundled ones of their lints
+        let unwrap_used = registry.rules("clippy::unwrap_used");
+        assert_eq!(unwrap_used.len(), 2);
+        assert_eq!(
+            unwrap_used[0].origin,
+            rules::Origin::Local(dir.join("unwrap_used.ssr"))
+        );
+        assert_eq!(
+            unwrap_used[1].origin,
+            rules::Origin::Local(dir.join("unwrap_used.txl"))
+        );
+        assert!(
+            matches!(&unwrap_used[1].transform, rules::Transform::Txl(text) if text == "% replaces the bundled rule")
+        );
+        assert_eq!(registry.rules("clippy::expect_used").len(), 1);
+        assert!(registry.rules("clippy::needless_return").is_empty());
+        let source = "fn main() {\n    let s = std::env::var(\"HOME\").unwrap();\n    println!(\"{s}\");\n}\n";
+        let main = dir.join("main.rs");
+        std::fs::write(&main, source).ok();
+        let warning = flagged(source, r#"std::env::var("HOME").unwrap()"#, "unwrap_used");
+        assert_eq!(
+            fix_by_rules(&main, &[warning], unwrap_used),
+            Some(transform::Tool::TreeSitter)
+        );
+        assert_eq!(
+            std::fs::read_to_string(&main).unwrap(),
+            "fn main() {\n    let s = std::env::var(\"HOME\").unwrap_or_default();\n    println!(\"{s}\");\n}\n"
+        );
+        let args = Args {
+            list_rules: true,
+            ..Default::default()
+        };
+        std::io::set_output_capture(Some(Default::default()));
+        run(args);
+        let captured = std::io::set_output_capture(None).unwrap();
+        let captured = Arc::try_unwrap(captured).unwrap();
+        let captured = captured.into_inner().unwrap();
+        let captured = String::from_utf8(captured).unwrap();
+        assert_eq!(
+            captured,
+            r###"lint                                     rule                                     origin
+clippy::as_conversions                   try_from                                 native
+clippy::cast_lossless                    from                                     native
+clippy::cast_possible_truncation         try_from                                 native
+clippy::cast_possible_wrap               try_from                                 native
+clippy::cast_sign_loss                   try_from                                 native
+clippy::dbg_macro                        dbg_macro                                native
+clippy::expect_used                      expect_used                              native
+clippy::float_cmp                        float_cmp                                native
+clippy::get_unwrap                       get_unwrap.ssr                           bundled
+clippy::unwrap_used                      unwrap_used                              native
+clippy::unwrap_used                      unwrap_used.txl                          bundled
+"###
+        );
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    #[serial]
+    fn test_rules() {
+        let fixtures = fixtures::fixtures(Path::new("fixtures")).unwrap();
+        assert!(fixtures
+            .iter()
+            .any(|f| f.lint == "unwrap_used" && f.name == "if_let"));
+        let args = Args {
+            test_rules: Some("fixtures".to_string()),
+            ..Default::default()
+        };
+        std::io::set_output_capture(Some(Default::default()));
+        run(args);
+        let captured = std::io::set_output_capture(None).unwrap();
+        let captured = Arc::try_unwrap(captured).unwrap();
+        let captured = captured.into_inner().unwrap();
+        let captured = String::from_utf8(captured).unwrap();
+        assert_eq!(
+            captured,
+            r###"pass cast_lossless/from
+pass cast_possible_truncation/try_from
+pass dbg_macro/value
+pass expect_used/context
+pass float_cmp/epsilon
+pass float_cmp/f32
+pass get_unwrap/index
+pass unwrap_used/if_let
+pass unwrap_used/question_mark
+9 of 9 fixtures passed
+"###
+        );
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    fn identity() {
+        let before = transform::items(
+            r#"
+fn a() -> i32 {
+    let x = 1;
+    let x = x + 1;
+    x
+}
+
+fn b() {
+    let y = 2;
+}
+"#,
+        );
+        let after = transform::items(
+            r#"
+fn c() {}
+
+fn a() -> i32 {
+    let x = 1;
+    x
+}
+"#,
+        );
+        let pairs: Vec<String> = transform::pair(&before, &after)
+            .iter()
+            .map(|pair| match pair {
+                (Some(b), Some(a)) => {
+                    format!("{} {} {}:{}", b.kind, b.path(), b.start_line, a.start_line)
+                }
+                (Some(b), None) => format!("removed {} {}", b.kind, b.path()),
+                (None, Some(a)) => format!("added {} {}", a.kind, a.path()),
+                (None, None) => String::new(),
+            })
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                "function_item a 2:4",
+                "let_declaration a::let x 3:5",
+                "removed let_declaration a::let x",
+                "removed function_item b",
+                "added function_item c",
+            ]
+        );
+    }
+
+    #[cfg(feature = "fix")]
+    #[test]
+    fn hunks() {
+        let input = "fn a() {\n    let x = 1;\n}\n\n\n\n\n\n\n\nfn b() {\n    let y = 2;\n}\n";
+        let output = "fn a() {\n    let x = 1;\n    let x = x + 1;\n    let x = x + 2;\n}\n\n\n\n\n\n\n\nfn b() {\n    let y = 3;\n}\n";
+        let (before, after) = (transform::items(input), transform::items(output));
+        let b = |items: &[transform::Item]| items.iter().find(|i| i.name == "b").cloned();
+        // the hunk of `a` is left out, so that the new lines of the hunk of `b` are not shifted
+        let (diff, applied) = transform::unified_diff(
+            "src/main.rs",
+            (input.as_bytes(), output.as_bytes()),
+            b(&before).as_ref(),
+            b(&after).as_ref(),
+        )
+        .unwrap();
+        assert_eq!(
+            diff,
+            concat!(
+                "--- a/src/main.rs\n",
+                "+++ b/src/main.rs\n",
+                "@@ -9,5 +9,5 @@ fn a() {\n",
+                " \n",
+                " \n",
+                " fn b() {\n",
+                "-    let y = 2;\n",
+                "+    let y = 3;\n",
+                " }\n",
+            )
+        );
+        assert_eq!(
+            String::from_utf8(applied).unwrap(),
+            input.replace("let y = 2", "let y = 3")
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn unfixed() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("The configuration file is: {s}");
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec![],
+                patch: Some(format!("{update_commit}")),
+                confirm: true,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"There are 1 warnings in 1 files.
+"###
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn revisions() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+"#,
+        ) {
+            checkout(update_commit);
+            let args = Args {
+                flags: vec![],
+                from: Some("HEAD~1".to_string()),
+                to: Some("HEAD".to_string()),
+                confirm: true,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"There are 1 warnings in 1 files.
+#[Warning(clippy::unwrap_used)
+@@ -1,5 +1,6 @@
+ 
+ fn main() {
+-    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+-    println!("{s}");
++    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
++        println!("{s}");
++    }
+ }
+"###
+            );
+            let repo = git2::Repository::open(".").unwrap();
+            assert_eq!(repo.head().unwrap().target(), Some(update_commit));
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn classify() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+    config();
+}
+
+fn config() {
+    let s = std::fs::read_to_string("Cargo.lock").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+    let home = std::env::var("HOME").expect("HOME is not set");
+    println!("{home}");
+    config();
+}
+
+#[allow(clippy::unwrap_used)]
+fn config() {
+    let s = std::fs::read_to_string("Cargo.lock").unwrap();
+    println!("{s}");
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec![],
+                patch: Some(format!("{update_commit}")),
+                classify: Some("table".to_string()),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"There are 2 warnings in 1 files.
+outcome    lint                                     before                         after
+fixed      clippy::unwrap_used                      src/main.rs:3-3                -
+suppressed clippy::unwrap_used                      src/main.rs:9-9                -
+introduced clippy::expect_used                      -                              src/main.rs:6-6
+"###
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn suggested() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let x: Vec<String> = std::env::args().collect();
+    println!("{}", x.len() == 0);
+}
+"#,
+            r#"
+fn main() {
+    let x: Vec<String> = std::env::args().collect();
+    println!("{}", x.is_empty());
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec![],
+                patch: Some(format!("{update_commit}")),
+                classify: Some("json".to_string()),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"[
+  {
+    "outcome": "fixed",
+    "lint": "clippy::len_zero",
+    "before": {
+      "file": "src/main.rs",
+      "start_line": 4,
+      "end_line": 4
+    },
+    "suggestions": [
+      {
+        "message": "using `is_empty` is clearer and more explicit",
+        "file": "src/main.rs",
+        "start_line": 4,
+        "end_line": 4,
+        "replacement": "x.is_empty()",
+        "applicability": "MachineApplicable"
+      }
+    ]
+  }
+]
+"###
+            );
+            let s = std::fs::read_to_string("diagnostics/src/main.rs").unwrap();
+            assert_eq!(
+                s,
+                r###"
+fn main() {
+    let x: Vec<String> = std::env::args().collect();
+    println!("{}", /*#[Warning(clippy::len_zero)*/x.len() == 0/*
+#[Warning(clippy::len_zero)
+suggestion: using `is_empty` is clearer and more explicit: x.is_empty()
+note: for further information visit https://rust-lang.github.io/rust-clippy/master/index.html#len_zero
+`#[warn(clippy::len_zero)]` on by default
+using `is_empty` is clearer and more explicit*/);
+}
+"###
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn suppressed() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+#[allow(clippy::unwrap_used)]
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec![],
+                patch: Some(format!("{update_commit}")),
+                confirm: true,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"There are 1 warnings in 1 files.
+"###
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn dirty() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+"#,
+        ) {
+            let work_in_progress = r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.lock").unwrap();
+    println!("{s}");
+}
+"#;
+            std::fs::write("src/main.rs", work_in_progress).ok();
+            std::fs::write("notes.txt", "untracked").ok();
+            let args = Args {
+                flags: vec![],
+                patch: Some(format!("{update_commit}")),
+                confirm: true,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"The work tree has uncommitted changes, please commit or stash them before using `--confirm`
+"###
+            );
+            assert_eq!(
+                std::fs::read_to_string("src/main.rs").unwrap(),
+                work_in_progress
+            );
+            assert_eq!(std::fs::read_to_string("notes.txt").unwrap(), "untracked");
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn renamed() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+mod util;
+
+fn main() {
+    println!("{}", util::config());
+}
+"#,
+            r#"
+mod helpers;
+
+fn main() {
+    println!("{}", helpers::config());
+}
+"#,
+        ) {
+            let util = r#"pub fn config() -> String {
+    std::fs::read_to_string("Cargo.lock").unwrap()
+}
+"#;
+            let before = commit_file("util", "src/util.rs", util).ok().unwrap();
+            // git mv src/util.rs src/helpers.rs
+            let repo = git2::Repository::open(std::path::Path::new(".")).unwrap();
+            let mut index = repo.index().unwrap();
+            std::fs::remove_file("src/util.rs").ok();
+            index.remove_path(std::path::Path::new("src/util.rs")).ok();
+            std::fs::write("src/helpers.rs", util).ok();
+            index.add_path(std::path::Path::new("src/helpers.rs")).ok();
+            index.write().ok();
+            let after = commit_file(
+                "rename",
+                "src/main.rs",
+                r#"
+mod helpers;
+
+fn main() {
+    println!("{}", helpers::config());
+}
+"#,
+            )
+            .ok()
+            .unwrap();
+            let args = Args {
+                flags: vec![],
+                from: Some(format!("{before}")),
+                to: Some(format!("{after}")),
+                classify: Some("table".to_string()),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"There are 1 warnings in 1 files.
+outcome    lint                                     before                         after
+untouched  clippy::unwrap_used                      src/util.rs:2-2                src/helpers.rs:2-2
+"###
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn mined() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+"#,
+        ) {
+            let args = || Args {
+                flags: vec![],
+                mine: Some(format!("{update_commit}")),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args());
+            // mining again resumes after the checkpointed commits
+            run(args());
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            // the manifest commit has no source file to build
+            assert_eq!(
+                captured,
+                r###"Mined 1 fixes from 3 commits into fixes.jsonl.
+1 commits could not be analysed, because they or their parents do not build.
+Resuming after 3 mined commits.
+Mined 0 fixes from 3 commits into fixes.jsonl.
+"###
+            );
+            let dataset = std::fs::read_to_string("fixes.jsonl").unwrap();
+            assert_eq!(dataset.lines().count(), 1);
+            let fix: serde_json::Value = serde_json::from_str(dataset.trim()).unwrap();
+            assert_eq!(fix["commit"], format!("{update_commit}"));
+            assert_eq!(fix["message"], "update");
+            assert_eq!(fix["lint"], "clippy::unwrap_used");
+            assert_eq!(fix["file"], "src/main.rs");
+            assert_eq!(fix["start_line"], 3);
+            assert_eq!(
+                fix["hunks"][0],
+                concat!(
+                    "@@ -1,5 +1,6 @@\n",
+                    " \n",
+                    " fn main() {\n",
+                    "-    let s = std::fs::read_to_string(\"Cargo.toml\").unwrap();\n",
+                    "-    println!(\"{s}\");\n",
+                    "+    if let Ok(s) = std::fs::read_to_string(\"Cargo.toml\") {\n",
+                    "+        println!(\"{s}\");\n",
+                    "+    }\n",
+                    " }\n"
+                )
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn mined_suppressed() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+#[allow(clippy::unwrap_used)]
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+        ) {
+            let args = |include_suppressed| Args {
+                flags: vec![],
+                mine: Some(format!("{update_commit}")),
+                include_suppressed,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args(false));
+            let without = std::fs::read_to_string("fixes.jsonl").unwrap();
+            std::fs::remove_file("fixes.jsonl").ok();
+            std::fs::remove_file("fixes.jsonl.checkpoint").ok();
+            run(args(true));
+            std::io::set_output_capture(None);
+            let with = std::fs::read_to_string("fixes.jsonl").unwrap();
+            teardown(cd, update_commit);
+            assert!(without.is_empty());
+            let fix: serde_json::Value = serde_json::from_str(with.trim()).unwrap();
+            assert_eq!(fix["lint"], "clippy::unwrap_used");
+            assert_eq!(fix["suppression"], "attribute");
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn batch() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+"#,
+        ) {
+            // the repositories are mined by the binary of the tool
+            Command::new("cargo")
+                .args(["build", "--quiet"])
+                .current_dir(&cd)
+                .status()
+                .ok();
+            std::fs::write("repos.txt", ".\nmissing\n").ok();
+            let args = |timeout| Args {
+                flags: vec![],
+                mine: Some(format!("{update_commit}")),
+                batch: Some("repos.txt".to_string()),
+                timeout,
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args(600));
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"repo                           status      records reason
+.                              succeeded         1 -
+missing                        failed            0 Not a directory
+Succeeded on 1 of 2 repositories, with 1 records in dataset.jsonl.
+"###
+            );
+            let dataset = std::fs::read_to_string("dataset.jsonl").unwrap();
+            let fix: serde_json::Value = serde_json::from_str(dataset.trim()).unwrap();
+            assert_eq!(fix["repo"], ".");
+            assert_eq!(fix["lint"], "clippy::unwrap_used");
+            assert_eq!(
+                std::fs::read_to_string("dataset.jsonl.d/summary.jsonl")
+                    .unwrap()
+                    .lines()
+                    .count(),
+                2
+            );
+            // a repository which runs out of time is killed, with the processes it has started
+            std::io::set_output_capture(Some(Default::default()));
+            run(args(0));
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert!(captured.contains(
+                ".                              timed out         0 Killed after 0 seconds"
+            ));
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn provenance() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+"#,
+        ) {
+            let init_commit = git2::Repository::open(".")
+                .unwrap()
+                .head()
+                .unwrap()
+                .target()
+                .unwrap();
+            std::fs::write(
+                "src/main.rs",
+                r#"
+fn config() -> String {
+    std::fs::read_to_string("Cargo.lock").unwrap()
+}
+
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}{}", config());
+}
+"#,
+            )
+            .ok();
+            let args = Args {
+                flags: vec![],
+                provenance: Some("table".to_string()),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                format!(
+                    r###"There are 2 warnings in 1 files.
+lint                                     location                       commit       age
+clippy::unwrap_used                      src/main.rs:3-3                -              0
+clippy::unwrap_used                      src/main.rs:7-7                {:<10}     0
+age of debt                                <1w   <1m   <6m   <1y   <2y  >=2y
+clippy::unwrap_used                          2     0     0     0     0     0
+"###,
+                    &init_commit.to_string()[..8]
+                )
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn shifted() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    let home = std::env::var("HOME").unwrap_or_default();
+    println!("{home}");
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec![],
+                patch: Some(format!("{update_commit}")),
+                confirm: true,
+                classify: Some("table".to_string()),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"There are 1 warnings in 1 files.
+outcome    lint                                     before                         after
+untouched  clippy::unwrap_used                      src/main.rs:3-3                src/main.rs:5-5
+"###
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn toolchains() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+"#,
+        ) {
+            // a toolchain that is not installed falls back to the current one
+            let declared = commit_file("toolchain", "rust-toolchain", "1.0.0\n")
+                .ok()
+                .unwrap();
+            let fixed = commit_file(
+                "fix",
+                "src/main.rs",
+                r#"
+fn main() {
+    if let Ok(s) = std::fs::read_to_string("Cargo.toml") {
+        println!("{s}");
+    }
+}
+"#,
+            )
+            .ok()
+            .unwrap();
+            let args = Args {
+                flags: vec![],
+                mine: Some(format!("{declared}..{fixed}")),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                r###"Mined 1 fixes from 1 commits into fixes.jsonl.
+"###
+            );
+            let dataset = std::fs::read_to_string("fixes.jsonl").unwrap();
+            let fix: serde_json::Value = serde_json::from_str(dataset.trim()).unwrap();
+            assert_ne!(fix["toolchain"]["name"], "1.0.0");
+            assert!(fix["toolchain"]["clippy"]
+                .as_str()
+                .unwrap()
+                .starts_with("clippy "));
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn unbuildable() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = std::fs::read_to_string("Cargo.toml").unwrap();
+    println!("{s}");
+}
+"#,
+            r#"
+fn main() {
+    let s: usize = std::fs::read_to_string("Cargo.toml");
+    println!("{s}");
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec![],
+                patch: Some(format!("{update_commit}")),
+                confirm: true,
+                classify: Some("table".to_string()),
+                ..Default::default()
+            };
+            std::io::set_output_capture(Some(Default::default()));
+            run(args);
+            let captured = std::io::set_output_capture(None).unwrap();
+            let captured = Arc::try_unwrap(captured).unwrap();
+            let captured = captured.into_inner().unwrap();
+            let captured = String::from_utf8(captured).unwrap();
+            assert_eq!(
+                captured,
+                format!(
+                    r###"There are 1 warnings in 1 files.
+The revision {update_commit} does not build, so the fixes cannot be confirmed
+outcome    lint                                     before                         after
+unanalysable clippy::unwrap_used                      src/main.rs:3-3                -
+"###
+                )
+            );
+            teardown(cd, update_commit);
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn mined_errors() {
+        if let Ok((cd, update_commit)) = setup(
+            r#"
+fn main() {
+    let s = String::from("Cargo.toml");
+    let t = s;
+    println!("{s} {t}");
+}
+"#,
+            r#"
+fn main() {
+    let s = String::from("Cargo.toml");
+    let t = s.clone();
+    println!("{s} {t}");
+}
+"#,
+        ) {
+            let args = Args {
+                flags: vec![],
+                mine: Some(format!("{update_commit}")),
+                errors: true,
+                output: Some("errors.jsonl".to_string()),
+                ..Default::default()
             };
             std::io::set_output_capture(Some(Default::default()));
             run(args);
@@ -985,8 +3348,29 @@ fn main() {
             let captured = Arc::try_unwrap(captured).unwrap();
             let captured = captured.into_inner().unwrap();
             let captured = String::from_utf8(captured).unwrap();
-            assert_eq!(captured, r###"There are 1 warnings in 1 files.
-"###);
+            assert_eq!(
+                captured,
+                r###"Mined 1 fixes from 3 commits into errors.jsonl.
+"###
+            );
+            let dataset = std::fs::read_to_string("errors.jsonl").unwrap();
+            let fix: serde_json::Value = serde_json::from_str(dataset.trim()).unwrap();
+            assert_eq!(fix["lint"], "E0382");
+            assert_eq!(fix["start_line"], 3);
+            assert_eq!(fix["end_line"], 5);
+            assert!(fix["rendered"]
+                .as_str()
+                .unwrap()
+                .starts_with("error[E0382]: borrow of moved value: `s`"));
+            assert!(fix["explanation"]
+                .as_str()
+                .unwrap()
+                .contains("A variable was used after its contents have been moved elsewhere."));
+            assert_eq!(fix["hunks"].as_array().unwrap().len(), 1);
+            assert!(fix["hunks"][0]
+                .as_str()
+                .unwrap()
+                .contains("+    let t = s.clone();"));
             teardown(cd, update_commit);
         }
     }
@@ -1013,9 +3397,12 @@ fn main() {
                     flags: vec![],
                     patch: None,
                     confirm: false,
+                    ..Default::default()
                 };
                 run(args);
-                assert!(!std::path::Path::new("test/transform/Wclippy::unwrap_used/0.2.rs").exists());
+                assert!(
+                    !std::path::Path::new("test/transform/Wclippy::unwrap_used/0.2.rs").exists()
+                );
                 std::env::set_current_dir(cd).ok();
             }
         }
@@ -0,0 +1,367 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use git2::{Commit, Diff, DiffFormat, Oid, Repository, Sort};
+use serde::Serialize;
+
+use crate::outcome::{classify, Outcome};
+use crate::revision::{diff, Revision, Snapshot};
+use crate::suppression::Suppression;
+use crate::toolchain::Toolchain;
+use crate::{Build, Ran, Suggested};
+
+// A warning fixed by a commit, as a record of the mined dataset
+#[derive(Debug, Clone, Serialize)]
+pub struct Fix {
+    pub commit: String,
+    pub parent: String,
+    // the author date of the commit, e.g. `2022-11-27T10:01:02+00:00`
+    pub date: String,
+    pub message: String,
+    pub lint: String,
+    pub file: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    // the hunks of the commit overlapping the lines of the warning
+    pub hunks: Vec<String>,
+    // the toolchain the commit has been diagnosed with
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub toolchain: Option<Toolchain>,
+    // the message of a resolved compiler error as printed by rustc, and the explanation of
+    // its error code as printed by `rustc --explain`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rendered: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub explanation: Option<String>,
+    // the replacements suggested by the children of the diagnostic of the warning
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggested>,
+    // how the warning has been silenced, with `--include-suppressed`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppression: Option<Suppression>,
+}
+
+// the commits mined so far, and the length of the output once their fixes had been written
+struct Checkpoint {
+    path: PathBuf,
+    done: HashSet<Oid>,
+    offset: u64,
+}
+
+impl Checkpoint {
+    // read the checkpoint next to the output, e.g. `fixes.jsonl.checkpoint`, which has a line
+    // `<commit id> <offset>` for every mined commit
+    fn load(output: &Path) -> Checkpoint {
+        let mut path = output.as_os_str().to_owned();
+        path.push(".checkpoint");
+        let mut checkpoint = Checkpoint {
+            path: PathBuf::from(path),
+            done: HashSet::new(),
+            offset: 0,
+        };
+        if let Ok(s) = std::fs::read_to_string(&checkpoint.path) {
+            for line in s.lines() {
+                if let Some((id, offset)) = line.split_once(' ') {
+                    if let (Ok(id), Ok(offset)) = (Oid::from_str(id), offset.parse()) {
+                        checkpoint.done.insert(id);
+                        checkpoint.offset = offset;
+                    }
+                }
+            }
+        }
+        checkpoint
+    }
+
+    fn save(&mut self, id: Oid, offset: u64) -> std::io::Result<()> {
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{id} {offset}")?;
+        self.done.insert(id);
+        self.offset = offset;
+        Ok(())
+    }
+}
+
+// the commits of a revspec, oldest first: `HEAD` stands for its whole history, and
+// `v0.1..main` for the commits of `main` since `v0.1`
+fn commits(repo: &Repository, range: &str) -> Result<Vec<Oid>, git2::Error> {
+    let mut walk = repo.revwalk()?;
+    walk.set_sorting(Sort::TOPOLOGICAL | Sort::REVERSE)?;
+    if range.contains("..") {
+        walk.push_range(range)?;
+    } else {
+        walk.push(repo.revparse_single(range)?.peel_to_commit()?.id())?;
+    }
+    walk.collect()
+}
+
+// the hunks of a diff by the old path of their files, with their old line ranges
+fn hunks(diff: &Diff) -> HashMap<String, Vec<(usize, usize, String)>> {
+    let mut hunks: HashMap<String, Vec<(usize, usize, String)>> = HashMap::new();
+    diff.print(DiffFormat::Patch, |delta, hunk, line| {
+        if let (Some(p), Some(h)) = (delta.old_file().path(), hunk) {
+            let file = p.to_string_lossy().to_string();
+            let content = String::from_utf8_lossy(line.content());
+            let hunks = hunks.entry(file).or_default();
+            match line.origin() {
+                'H' => hunks.push((
+                    usize::try_from(h.old_start()).unwrap_or(0),
+                    usize::try_from(h.old_lines()).unwrap_or(0),
+                    content.to_string(),
+                )),
+                origin @ (' ' | '+' | '-') => {
+                    if let Some((_, _, text)) = hunks.last_mut() {
+                        text.push(origin);
+                        text.push_str(&content);
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    })
+    .ok();
+    hunks
+}
+
+// format a git time as RFC 3339 in its own time zone
+pub fn date(time: git2::Time) -> String {
+    let offset = i64::from(time.offset_minutes());
+    let local = time.seconds() + offset * 60;
+    let (days, seconds) = (local.div_euclid(86400), local.rem_euclid(86400));
+    // the civil date of the days since 1970-01-01, after Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}{}{:02}:{:02}",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60,
+        if offset < 0 { '-' } else { '+' },
+        offset.abs() / 60,
+        offset.abs() % 60
+    )
+}
+
+// the fixes of the warnings, or of the compiler errors, of its parent made by a commit, with
+// the warnings it has suppressed when they are included
+fn fixes(
+    repo: &Repository,
+    commit: &Commit,
+    diff: &Diff,
+    before: &HashMap<String, Vec<Ran>>,
+    after: &HashMap<String, Vec<Ran>>,
+    toolchain: Option<&Toolchain>,
+    (errors, include_suppressed): (bool, bool),
+) -> Vec<Fix> {
+    let hunks = hunks(diff);
+    let mut fixes = Vec::new();
+    for c in classify(repo, diff, before, after) {
+        let fixed = match c.outcome {
+            Outcome::Fixed => true,
+            Outcome::Suppressed => include_suppressed,
+            _ => false,
+        };
+        if let (true, Some(l)) = (fixed, c.before) {
+            let diagnostic = before.get(&l.file).and_then(|v| {
+                v.iter().find(|r| {
+                    r.lint() == c.lint && r.start_line == l.start_line && r.end_line == l.end_line
+                })
+            });
+            if diagnostic.is_some_and(|r| r.is_error()) != errors {
+                continue;
+            }
+            let (rendered, explanation) = match diagnostic {
+                Some(r) if errors => (Some(r.rendered.clone()), Some(r.explanation.clone())),
+                _ => (None, None),
+            };
+            let overlapping: Vec<String> = hunks
+                .get(&l.file)
+                .map(|h| {
+                    h.iter()
+                        .filter(|(start, lines, _)| {
+                            *start <= l.end_line && start + lines >= l.start_line
+                        })
+                        .map(|(_, _, text)| text.clone())
+                        .collect()
+                })
+                .unwrap_or_default();
+            // an error is reported once, with the hunks of all of its spans
+            if let Some(f) = fixes.iter_mut().find(|f: &&mut Fix| {
+                rendered.is_some() && f.rendered == rendered && f.file == l.file
+            }) {
+                f.start_line = f.start_line.min(l.start_line);
+                f.end_line = f.end_line.max(l.end_line);
+                for h in overlapping {
+                    if !f.hunks.contains(&h) {
+                        f.hunks.push(h);
+                    }
+                }
+                continue;
+            }
+            fixes.push(Fix {
+                commit: commit.id().to_string(),
+                parent: commit
+                    .parent_id(0)
+                    .map(|p| p.to_string())
+                    .unwrap_or_default(),
+                date: date(commit.author().when()),
+                message: commit.message().unwrap_or("").to_string(),
+                lint: c.lint,
+                hunks: overlapping,
+                file: l.file,
+                start_line: l.start_line,
+                end_line: l.end_line,
+                toolchain: toolchain.cloned(),
+                rendered,
+                explanation,
+                suggestions: diagnostic
+                    .map(|r| r.suggestions.clone())
+                    .unwrap_or_default(),
+                suppression: c.suppression,
+            });
+        }
+    }
+    fixes
+}
+
+// The numbers of a mining run
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Mined {
+    pub fixes: usize,
+    pub commits: usize,
+    // the commits that could not be compared with their parents because either does not build
+    pub unanalysable: usize,
+}
+
+// Mine the confirmed fixes of every non-merge commit of the range into the JSONL output,
+// resuming after the commits of its checkpoint. With `errors`, the compiler errors of the
+// parents resolved by commits that build are mined instead of the warnings, and with
+// `include_suppressed` the warnings silenced by lint attributes or configuration are mined too.
+pub fn mine(
+    repo: &Repository,
+    range: &str,
+    flags: &[String],
+    retry_check: bool,
+    (errors, include_suppressed): (bool, bool),
+    output: &Path,
+) -> Result<Mined, git2::Error> {
+    let error = |e: std::io::Error| git2::Error::from_str(&e.to_string());
+    let mut checkpoint = Checkpoint::load(output);
+    // drop the fixes of a commit interrupted before it was checkpointed
+    let file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .truncate(false)
+        .open(output)
+        .map_err(error)?;
+    file.set_len(checkpoint.offset).map_err(error)?;
+    drop(file);
+    let commits = commits(repo, range)?;
+    if !checkpoint.done.is_empty() {
+        println!("Resuming after {} mined commits.", checkpoint.done.len());
+    }
+    let mut mined = Mined {
+        commits: commits.len(),
+        ..Default::default()
+    };
+    // the warnings of the previous commit, which is usually the parent of the next one
+    let mut previous: Option<(Oid, HashMap<String, Vec<Ran>>, Build)> = None;
+    type Diagnosed = (HashMap<String, Vec<Ran>>, Build, Option<Toolchain>);
+    let diagnose = |id: Oid| -> Result<Diagnosed, git2::Error> {
+        let tree = repo.find_commit(id)?.tree_id();
+        let snapshot = Snapshot::export(repo, &Revision::Tree(tree))?;
+        let (warnings, build) = snapshot.diagnose(flags.to_vec(), retry_check);
+        Ok((warnings, build, snapshot.toolchain.clone()))
+    };
+    for id in &commits {
+        if checkpoint.done.contains(id) {
+            continue;
+        }
+        let commit = repo.find_commit(*id)?;
+        let mut records = Vec::new();
+        if commit.parent_count() == 1 {
+            let parent = commit.parent(0)?;
+            let (before, built) = match previous.take() {
+                Some((p, warnings, build)) if p == parent.id() => (warnings, build),
+                _ => {
+                    let (warnings, build, _) = diagnose(parent.id())?;
+                    (warnings, build)
+                }
+            };
+            let diff = diff(
+                repo,
+                &Revision::Tree(parent.tree_id()),
+                &Revision::Tree(commit.tree_id()),
+            )?;
+            let touches = |changed: &dyn Fn(&Path) -> bool| {
+                diff.deltas().any(|d| {
+                    [d.old_file().path(), d.new_file().path()]
+                        .iter()
+                        .flatten()
+                        .any(|p| changed(p))
+                })
+            };
+            let code = touches(&|p| p.extension().is_some_and(|e| e == "rs"));
+            // `Cargo.toml`, `clippy.toml` or `rust-toolchain.toml`, or the legacy
+            // `rust-toolchain` file without an extension
+            let configuration = touches(&|p| {
+                p.extension().is_some_and(|e| e == "toml")
+                    || p.file_name().is_some_and(|n| n == "rust-toolchain")
+            });
+            if !code && !configuration {
+                // neither the code nor the lint configuration or the toolchain has changed
+                previous = Some((*id, before, built));
+            } else if !code {
+                // a commit without changes to Rust code cannot fix any of the warnings
+            } else if !errors && !built.analysable() {
+                mined.unanalysable += 1;
+            } else if before.values().flatten().any(|r| r.is_error() == errors) {
+                let (after, build, toolchain) = diagnose(*id)?;
+                // the errors of a commit that still does not build may only be hidden by others
+                if build.analysable() {
+                    records = fixes(
+                        repo,
+                        &commit,
+                        &diff,
+                        &before,
+                        &after,
+                        toolchain.as_ref(),
+                        (errors, include_suppressed),
+                    );
+                } else {
+                    mined.unanalysable += 1;
+                }
+                previous = Some((*id, after, build));
+            }
+        }
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(output)
+            .map_err(error)?;
+        for r in &records {
+            if let Ok(s) = serde_json::to_string(r) {
+                writeln!(file, "{s}").map_err(error)?;
+            }
+        }
+        file.sync_data().map_err(error)?;
+        let offset = file.metadata().map_err(error)?.len();
+        checkpoint.save(*id, offset).map_err(error)?;
+        mined.fixes += records.len();
+    }
+    Ok(mined)
+}
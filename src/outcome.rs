@@ -0,0 +1,253 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use git2::{Delta, Diff, Repository};
+use serde::Serialize;
+
+use crate::revision::{renamed, renames, LineMap};
+use crate::suppression::{Suppression, Suppressions};
+use crate::{Ran, Suggested};
+
+// What became of a warning of the first revision in the second one
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    // the warning is gone after a code change
+    Fixed,
+    // the warning is gone because it has been allowed or expected, see `Suppression`
+    Suppressed,
+    // the file with the warning has been deleted
+    Deleted,
+    // the flagged code has been moved elsewhere, and the warning came along with it
+    Moved,
+    // the warning is still reported at the same lines
+    Untouched,
+    // the warning is only reported by the second revision
+    Introduced,
+    // the second revision does not build, so that its warnings are unknown
+    Unanalysable,
+}
+
+impl Display for Outcome {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        f.write_str(match self {
+            Outcome::Fixed => "fixed",
+            Outcome::Suppressed => "suppressed",
+            Outcome::Deleted => "deleted",
+            Outcome::Moved => "moved",
+            Outcome::Untouched => "untouched",
+            Outcome::Introduced => "introduced",
+            Outcome::Unanalysable => "unanalysable",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Location {
+    pub file: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl Location {
+    fn new(file: &str, r: &Ran) -> Location {
+        Location {
+            file: file.to_string(),
+            start_line: r.start_line,
+            end_line: r.end_line,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut Formatter<'_>) -> Result<(), std::fmt::Error> {
+        write!(f, "{}:{}-{}", self.file, self.start_line, self.end_line)
+    }
+}
+
+// A warning with its outcome, located in the first revision (`before`), in the second
+// revision (`after`), or in both
+#[derive(Debug, Clone, Serialize)]
+pub struct Classified {
+    pub outcome: Outcome,
+    pub lint: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Location>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Location>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suppression: Option<Suppression>,
+    // the new path of the file of a warning before the diff, when it has been renamed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub renamed_to: Option<String>,
+    // the replacements suggested by the children of the diagnostic
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub suggestions: Vec<Suggested>,
+}
+
+// the files deleted by a diff
+fn deleted(diff: &Diff) -> HashSet<String> {
+    let mut deleted = HashSet::new();
+    for delta in diff.deltas() {
+        if delta.status() == Delta::Deleted {
+            if let Some(p) = delta.old_file().path() {
+                deleted.insert(p.to_string_lossy().to_string());
+            }
+        }
+    }
+    deleted
+}
+
+// whether a warning after the diff overlaps with the lines of a warning before it
+fn overlaps(lines: &LineMap, file: &str, a: &Ran, b: &Ran) -> bool {
+    let (start_line, end_line) = lines.span(file, a.start_line, a.end_line);
+    start_line <= b.end_line && end_line >= b.start_line
+}
+
+fn sorted(map: &HashMap<String, Vec<Ran>>) -> Vec<(&String, &Vec<Ran>)> {
+    let mut files: Vec<(&String, &Vec<Ran>)> = map.iter().collect();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+    files
+}
+
+// Classify every warning `before` the diff by what became of it `after` the diff, followed
+// by the warnings introduced by the diff
+pub fn classify(
+    repo: &Repository,
+    diff: &Diff,
+    before: &HashMap<String, Vec<Ran>>,
+    after: &HashMap<String, Vec<Ran>>,
+) -> Vec<Classified> {
+    let deleted = deleted(diff);
+    let renames = renames(diff);
+    let lines = LineMap::new(diff);
+    let suppressions = Suppressions::from_diff(repo, diff);
+    let before = sorted(before);
+    let after = sorted(after);
+    // the warnings after the diff that have been traced back to a warning before it
+    let mut matched: HashSet<(usize, usize)> = HashSet::new();
+    let mut traced: HashMap<(usize, usize), (Outcome, Location)> = HashMap::new();
+    // warnings still reported in place, once shifted by the lines added or removed above them,
+    // take precedence over moved ones
+    for (i, (file, warnings)) in before.iter().enumerate() {
+        for (j, w) in warnings.iter().enumerate() {
+            if let Some((k, (f2, v2))) = after
+                .iter()
+                .enumerate()
+                .find(|(_, (f2, _))| f2.as_str() == renamed(&renames, file))
+            {
+                if let Some((l, m)) = v2.iter().enumerate().find(|(l, m)| {
+                    m.name == w.name && overlaps(&lines, file, w, m) && !matched.contains(&(k, *l))
+                }) {
+                    matched.insert((k, l));
+                    traced.insert((i, j), (Outcome::Untouched, Location::new(f2, m)));
+                }
+            }
+        }
+    }
+    for (i, (_, warnings)) in before.iter().enumerate() {
+        for (j, w) in warnings.iter().enumerate() {
+            if traced.contains_key(&(i, j)) {
+                continue;
+            }
+            'moved: for (k, (f2, v2)) in after.iter().enumerate() {
+                for (l, m) in v2.iter().enumerate() {
+                    if m.name == w.name && m.code == w.code && !matched.contains(&(k, l)) {
+                        matched.insert((k, l));
+                        traced.insert((i, j), (Outcome::Moved, Location::new(f2, m)));
+                        break 'moved;
+                    }
+                }
+            }
+        }
+    }
+    let mut records = Vec::new();
+    for (i, (file, warnings)) in before.iter().enumerate() {
+        for (j, w) in warnings.iter().enumerate() {
+            let suppression = suppressions.of(file, w);
+            let (outcome, location) = match traced.remove(&(i, j)) {
+                Some((outcome, location)) => (outcome, Some(location)),
+                None if deleted.contains(*file) => (Outcome::Deleted, None),
+                None if suppression.is_some() => (Outcome::Suppressed, None),
+                None => (Outcome::Fixed, None),
+            };
+            records.push(Classified {
+                outcome,
+                lint: w.lint().to_string(),
+                before: Some(Location::new(file, w)),
+                after: location,
+                suppression: if outcome == Outcome::Suppressed {
+                    suppression
+                } else {
+                    None
+                },
+                renamed_to: renames.get(*file).cloned(),
+                suggestions: w.suggestions.clone(),
+            });
+        }
+    }
+    for (k, (file, warnings)) in after.iter().enumerate() {
+        for (l, m) in warnings.iter().enumerate() {
+            if !matched.contains(&(k, l)) {
+                records.push(Classified {
+                    outcome: Outcome::Introduced,
+                    lint: m.lint().to_string(),
+                    before: None,
+                    after: Some(Location::new(file, m)),
+                    suppression: None,
+                    renamed_to: None,
+                    suggestions: m.suggestions.clone(),
+                });
+            }
+        }
+    }
+    records
+}
+
+// The warnings before a diff to a revision that does not build
+pub fn unanalysable(before: &HashMap<String, Vec<Ran>>) -> Vec<Classified> {
+    let mut records = Vec::new();
+    for (file, warnings) in sorted(before) {
+        for w in warnings {
+            records.push(Classified {
+                outcome: Outcome::Unanalysable,
+                lint: w.lint().to_string(),
+                before: Some(Location::new(file, w)),
+                after: None,
+                suppression: None,
+                renamed_to: None,
+                suggestions: w.suggestions.clone(),
+            });
+        }
+    }
+    records
+}
+
+fn location(l: &Option<Location>) -> String {
+    match l {
+        Some(l) => l.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+pub fn print_table(records: &[Classified]) {
+    println!("{:<10} {:<40} {:<30} after", "outcome", "lint", "before");
+    for r in records {
+        println!(
+            "{:<10} {:<40} {:<30} {}",
+            r.outcome.to_string(),
+            r.lint,
+            location(&r.before),
+            match (&r.after, &r.renamed_to) {
+                (None, Some(file)) => format!("-> {file}"),
+                (after, _) => location(after),
+            }
+        );
+    }
+}
+
+pub fn print_json(records: &[Classified]) {
+    if let Ok(s) = serde_json::to_string_pretty(records) {
+        println!("{s}");
+    }
+}
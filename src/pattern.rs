@@ -0,0 +1,229 @@
+use std::collections::HashMap;
+
+use tree_sitter::{Node, Tree};
+
+use crate::language::Language;
+use crate::rewrite::{self, function, returned, Replacement};
+use crate::Ran;
+
+// the identifiers standing for the metavariables of a pattern, e.g. `__metavariable_e` for
+// `$e`, so that the pattern parses as Rust
+const PREFIX: &str = "__metavariable_";
+
+// the function wrapping the expression of a pattern for it to be parsed on its own
+const WRAPPER: (&str, &str) = ("fn f() { ", " }");
+
+// A structural search-and-replace pattern, e.g. `$e.unwrap() ==>> $e? where returns Result`:
+// the expressions matching the search pattern, with their metavariables bound to any
+// expressions, become the replacement with the metavariables substituted, provided that the
+// function enclosing them returns the type of the `where` clause, if any
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pattern {
+    pub search: String,
+    pub replace: String,
+    pub returns: Option<String>,
+}
+
+fn text<'a>(node: &Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+// the text with each metavariable `$name` replaced by its substitute
+fn substitute(text: &str, mut f: impl FnMut(&str) -> String) -> String {
+    let mut output = String::new();
+    let mut rest = text;
+    while let Some(i) = rest.find('$') {
+        output.push_str(&rest[..i]);
+        let after = &rest[i + 1..];
+        let n = after
+            .find(|c: char| !(c.is_alphanumeric() || c == '_'))
+            .unwrap_or(after.len());
+        if n == 0 {
+            output.push('$');
+        } else {
+            output.push_str(&f(&after[..n]));
+        }
+        rest = &after[n..];
+    }
+    output.push_str(rest);
+    output
+}
+
+// the names of the metavariables of the text, e.g. `e` for `$e.unwrap()`
+fn metavariables(text: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    substitute(text, |name| {
+        names.push(name.to_string());
+        String::new()
+    });
+    names
+}
+
+// the search pattern as Rust code, wrapped in a function, and its syntax tree
+fn tree(search: &str) -> Result<(String, Tree), String> {
+    let code = format!(
+        "{}{}{}",
+        WRAPPER.0,
+        substitute(search, |name| format!("{PREFIX}{name}")),
+        WRAPPER.1
+    );
+    let tree = Language::Rust.parse(&code).map_err(|e| e.to_string())?;
+    if tree.root_node().has_error() || expression(&tree).is_none() {
+        return Err(format!("cannot parse `{search}` as an expression"));
+    }
+    Ok((code, tree))
+}
+
+// the expression of the function wrapping a search pattern
+fn expression(tree: &Tree) -> Option<Node<'_>> {
+    let body = tree
+        .root_node()
+        .named_child(0)?
+        .child_by_field_name("body")?;
+    if body.named_child_count() == 1 {
+        body.named_child(0)
+    } else {
+        None
+    }
+}
+
+// Parse the patterns of a rule, one per line, skipping the blank lines and the comments
+// starting with `#`
+pub fn parse(text: &str) -> Result<Vec<Pattern>, String> {
+    let mut patterns = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let n = i + 1;
+        let Some((search, replace)) = line.split_once("==>>") else {
+            return Err(format!("line {n}: expected `<search> ==>> <replace>`"));
+        };
+        let (replace, returns) = match replace.rsplit_once(" where returns ") {
+            Some((replace, returns)) => (replace, Some(returns.trim().to_string())),
+            None => (replace, None),
+        };
+        let (search, replace) = (search.trim(), replace.trim());
+        tree(search).map_err(|e| format!("line {n}: {e}"))?;
+        let bound = metavariables(search);
+        if let Some(name) = metavariables(replace).iter().find(|m| !bound.contains(m)) {
+            return Err(format!("line {n}: ${name} is not bound by `{search}`"));
+        }
+        patterns.push(Pattern {
+            search: search.to_string(),
+            replace: replace.to_string(),
+            returns,
+        });
+    }
+    Ok(patterns)
+}
+
+// the children of a node which make up its syntax, without the comments
+fn children(node: Node) -> Vec<Node> {
+    let mut cursor = node.walk();
+    node.children(&mut cursor)
+        .filter(|c| !c.kind().ends_with("_comment"))
+        .collect()
+}
+
+// Whether a node of the source matches a node of the pattern, binding the metavariables of
+// the pattern to the texts of the nodes they stand for; a metavariable occurring twice must
+// stand for the same text
+fn matches(
+    (pattern, code): (Node, &str),
+    (node, source): (Node, &str),
+    bindings: &mut HashMap<String, String>,
+) -> bool {
+    let expected = text(&pattern, code);
+    if pattern.child_count() == 0 {
+        if let Some(name) = expected.strip_prefix(PREFIX) {
+            let actual = text(&node, source);
+            return match bindings.get(name) {
+                Some(bound) => bound == actual,
+                None => {
+                    bindings.insert(name.to_string(), actual.to_string());
+                    true
+                }
+            };
+        }
+    }
+    if pattern.kind() != node.kind() {
+        return false;
+    }
+    let (expected_children, actual_children) = (children(pattern), children(node));
+    if expected_children.is_empty() || actual_children.is_empty() {
+        return expected_children.is_empty()
+            && actual_children.is_empty()
+            && expected == text(&node, source);
+    }
+    expected_children.len() == actual_children.len()
+        && expected_children
+            .into_iter()
+            .zip(actual_children)
+            .all(|(p, n)| matches((p, code), (n, source), bindings))
+}
+
+// the nodes spanning the warning, from the outermost one, then the nodes inside them in order
+fn candidates<'a>(root: Node<'a>, w: &Ran) -> Vec<Node<'a>> {
+    let Some(mut node) = root.descendant_for_byte_range(w.start, w.end) else {
+        return Vec::new();
+    };
+    while let Some(parent) = node.parent() {
+        if parent.byte_range() != node.byte_range() {
+            break;
+        }
+        node = parent;
+    }
+    let mut nodes = Vec::new();
+    let mut stack = vec![node];
+    while let Some(n) = stack.pop() {
+        if w.start <= n.start_byte() && n.end_byte() <= w.end {
+            nodes.push(n);
+        }
+        let mut cursor = n.walk();
+        let mut named: Vec<Node> = n.named_children(&mut cursor).collect();
+        named.reverse();
+        stack.extend(named);
+    }
+    nodes
+}
+
+// whether the function enclosing the node returns the type, `()` for no value
+fn returning(node: Node, source: &str, ty: &str) -> bool {
+    function(node).is_some_and(|f| returned(f, source).unwrap_or("()") == ty)
+}
+
+// Replace the first node of the warning matching one of the patterns, the outer nodes first
+fn replace(source: &str, w: &Ran, patterns: &[(&Pattern, String, Tree)]) -> Option<Replacement> {
+    let tree = Language::Rust.parse(source).ok()?;
+    for node in candidates(tree.root_node(), w) {
+        for (pattern, code, search) in patterns {
+            let mut bindings = HashMap::new();
+            let Some(expected) = expression(search) else {
+                continue;
+            };
+            if matches((expected, code), (node, source), &mut bindings)
+                && pattern
+                    .returns
+                    .as_deref()
+                    .is_none_or(|ty| returning(node, source, ty))
+            {
+                let replacement = substitute(&pattern.replace, |name| {
+                    bindings.get(name).cloned().unwrap_or_default()
+                });
+                return Some((node.start_byte(), node.end_byte(), replacement));
+            }
+        }
+    }
+    None
+}
+
+// Rewrite the warnings in the source by the patterns of their lint
+pub fn rewrite(source: &str, warnings: &[Ran], patterns: &[Pattern]) -> String {
+    let patterns: Vec<(&Pattern, String, Tree)> = patterns
+        .iter()
+        .filter_map(|p| tree(&p.search).ok().map(|(code, tree)| (p, code, tree)))
+        .collect();
+    rewrite::rewrite(source, warnings, |source, w| replace(source, w, &patterns))
+}
@@ -0,0 +1,201 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use git2::{BlameOptions, DiffOptions, Patch, Repository};
+use serde::Serialize;
+
+use crate::mine::date;
+use crate::Ran;
+
+// the upper bounds in days of the buckets of the histogram, and their labels
+const BUCKETS: [(i64, &str); 6] = [
+    (7, "<1w"),
+    (30, "<1m"),
+    (182, "<6m"),
+    (365, "<1y"),
+    (730, "<2y"),
+    (i64::MAX, ">=2y"),
+];
+
+// A warning with the commit that last changed its lines
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    pub lint: String,
+    pub file: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    // `None` when some of the lines have not been committed yet
+    pub commit: Option<String>,
+    pub date: Option<String>,
+    // the days from the commit to HEAD
+    pub age: i64,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub warnings: Vec<Provenance>,
+    // the number of warnings of each lint by their age
+    pub histogram: BTreeMap<String, BTreeMap<&'static str, usize>>,
+}
+
+fn bucket(age: i64) -> &'static str {
+    BUCKETS
+        .iter()
+        .find(|(days, _)| age < *days)
+        .map_or(">=2y", |(_, label)| label)
+}
+
+// the hunks changing the committed blob of a file into its content in the work tree, as the
+// line ranges `(old_start, old_lines, new_start, new_lines)`
+fn changes(
+    blob: &git2::Blob,
+    file: &str,
+    content: &[u8],
+) -> Result<Vec<(usize, usize, usize, usize)>, git2::Error> {
+    let mut opts = DiffOptions::new();
+    opts.context_lines(0);
+    let patch = Patch::from_blob_and_buffer(
+        blob,
+        Some(Path::new(file)),
+        content,
+        Some(Path::new(file)),
+        Some(&mut opts),
+    )?;
+    let mut hunks = Vec::new();
+    for i in 0..patch.num_hunks() {
+        let (h, _) = patch.hunk(i)?;
+        hunks.push((
+            usize::try_from(h.old_start()).unwrap_or(0),
+            usize::try_from(h.old_lines()).unwrap_or(0),
+            usize::try_from(h.new_start()).unwrap_or(0),
+            usize::try_from(h.new_lines()).unwrap_or(0),
+        ));
+    }
+    Ok(hunks)
+}
+
+// the committed line of a line of the work tree, unless it has been added or changed since
+fn committed_line(hunks: &[(usize, usize, usize, usize)], line: usize) -> Option<usize> {
+    let mut committed = line;
+    for (old_start, old_lines, new_start, new_lines) in hunks {
+        // a hunk without lines on one side comes after its line number on that side
+        let new_end = new_start + new_lines.max(&1);
+        let old_end = old_start + old_lines.max(&1);
+        if line < new_end {
+            if *new_lines > 0 && line >= *new_start {
+                return None;
+            }
+            break;
+        }
+        committed = line + old_end - new_end;
+    }
+    Some(committed)
+}
+
+// Blame the lines of every warning of the work tree: a warning has existed at least since the
+// most recent commit of its lines, because it is only reported once all of them are there
+pub fn provenance(
+    repo: &Repository,
+    warnings: &HashMap<String, Vec<Ran>>,
+) -> Result<Report, git2::Error> {
+    let head = repo.head()?.peel_to_commit()?;
+    let now = head.time().seconds();
+    let workdir = repo.workdir().unwrap_or_else(|| Path::new("."));
+    let mut files: Vec<(&String, &Vec<Ran>)> = warnings.iter().collect();
+    files.sort_by(|a, b| a.0.cmp(b.0));
+    let mut report = Report {
+        warnings: Vec::new(),
+        histogram: BTreeMap::new(),
+    };
+    for (file, warnings) in files {
+        let content = std::fs::read(workdir.join(file)).unwrap_or_default();
+        let mut opts = BlameOptions::new();
+        opts.newest_commit(head.id());
+        // an untracked file has not been committed at all
+        let blame = repo.blame_file(Path::new(file), Some(&mut opts)).ok();
+        let hunks = head
+            .tree()?
+            .get_path(Path::new(file))
+            .and_then(|entry| repo.find_blob(entry.id()))
+            .and_then(|blob| changes(&blob, file, &content))
+            .ok();
+        for w in warnings {
+            let mut newest: Option<(git2::Oid, git2::Time)> = None;
+            let mut uncommitted = blame.is_none();
+            for line in w.start_line..=w.end_line {
+                let committed = hunks.as_ref().and_then(|h| committed_line(h, line));
+                match (blame.as_ref(), committed) {
+                    (Some(b), Some(line)) => b.get_line(line),
+                    _ => None,
+                }
+                .map_or_else(
+                    || uncommitted = true,
+                    |hunk| {
+                        let when = hunk.final_signature().when();
+                        if newest.is_none_or(|(_, t)| t.seconds() < when.seconds()) {
+                            newest = Some((hunk.final_commit_id(), when));
+                        }
+                    },
+                );
+            }
+            let (commit, date, age) = match newest {
+                Some((id, when)) if !uncommitted => (
+                    Some(id.to_string()),
+                    Some(date(when)),
+                    (now - when.seconds()).max(0) / 86400,
+                ),
+                _ => (None, None, 0),
+            };
+            *report
+                .histogram
+                .entry(w.lint().to_string())
+                .or_insert_with(|| BUCKETS.iter().map(|(_, label)| (*label, 0)).collect())
+                .entry(bucket(age))
+                .or_default() += 1;
+            report.warnings.push(Provenance {
+                lint: w.lint().to_string(),
+                file: file.clone(),
+                start_line: w.start_line,
+                end_line: w.end_line,
+                commit,
+                date,
+                age,
+            });
+        }
+    }
+    Ok(report)
+}
+
+pub fn print_table(report: &Report) {
+    println!(
+        "{:<40} {:<30} {:<10} {:>5}",
+        "lint", "location", "commit", "age"
+    );
+    for p in &report.warnings {
+        println!(
+            "{:<40} {:<30} {:<10} {:>5}",
+            p.lint,
+            format!("{}:{}-{}", p.file, p.start_line, p.end_line),
+            p.commit.as_deref().map_or("-", |c| &c[..c.len().min(8)]),
+            p.age
+        );
+    }
+    print!("{:<40}", "age of debt");
+    for (_, label) in BUCKETS {
+        print!(" {label:>5}");
+    }
+    println!();
+    for (lint, counts) in &report.histogram {
+        print!("{lint:<40}");
+        for (_, label) in BUCKETS {
+            print!(" {:>5}", counts.get(label).unwrap_or(&0));
+        }
+        println!();
+    }
+}
+
+pub fn print_json(report: &Report) {
+    if let Ok(s) = serde_json::to_string_pretty(report) {
+        println!("{s}");
+    }
+}
@@ -0,0 +1,302 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use git2::{Delta, Diff, DiffFindOptions, DiffFormat, DiffOptions, Repository, StatusOptions};
+
+use crate::toolchain::{self, Toolchain};
+use crate::{diagnose_all_warnings, Build, Ran};
+
+// A side of a comparison: either a tree in the object database (a commit, a tag, a branch,
+// `HEAD~3`, or the index written out as a tree), or the files in the work tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Revision {
+    Tree(git2::Oid),
+    WorkTree,
+}
+
+impl Revision {
+    // resolve a revspec, where `INDEX` stands for the staged changes and `WORKTREE` for the
+    // files on disk
+    pub fn parse(repo: &Repository, spec: &str) -> Result<Revision, git2::Error> {
+        match spec {
+            "WORKTREE" => Ok(Revision::WorkTree),
+            "INDEX" => Ok(Revision::Tree(repo.index()?.write_tree()?)),
+            _ => Ok(Revision::Tree(
+                repo.revparse_single(spec)?.peel_to_tree()?.id(),
+            )),
+        }
+    }
+}
+
+// the differences between two revisions, including the untracked files of the work tree,
+// with renamed and copied files paired up
+pub fn diff<'r>(
+    repo: &'r Repository,
+    from: &Revision,
+    to: &Revision,
+) -> Result<Diff<'r>, git2::Error> {
+    let mut diff = raw_diff(repo, from, to)?;
+    diff.find_similar(Some(
+        DiffFindOptions::new()
+            .renames(true)
+            .copies(true)
+            .for_untracked(true),
+    ))?;
+    Ok(diff)
+}
+
+// the new paths of the renamed files, by their old paths
+pub fn renames(diff: &Diff) -> HashMap<String, String> {
+    let mut renames = HashMap::new();
+    for delta in diff.deltas() {
+        if delta.status() == Delta::Renamed {
+            if let (Some(old), Some(new)) = (delta.old_file().path(), delta.new_file().path()) {
+                renames.insert(
+                    old.to_string_lossy().to_string(),
+                    new.to_string_lossy().to_string(),
+                );
+            }
+        }
+    }
+    renames
+}
+
+// the path of a file after the diff
+pub fn renamed<'a>(renames: &'a HashMap<String, String>, file: &'a str) -> &'a str {
+    renames.get(file).map_or(file, |f| f.as_str())
+}
+
+// the lines removed from and added to a file at a position, without the context lines
+#[derive(Debug, Clone, Copy, Default)]
+struct Segment {
+    old_start: usize,
+    old_lines: usize,
+    new_start: usize,
+    new_lines: usize,
+}
+
+// The lines of the files before a diff mapped to their lines after it, by their old paths
+#[derive(Debug, Default)]
+pub struct LineMap(HashMap<String, Vec<Segment>>);
+
+impl LineMap {
+    pub fn new(diff: &Diff) -> LineMap {
+        let mut map: HashMap<String, Vec<Segment>> = HashMap::new();
+        // the next line numbers on each side, and the segment of changed lines being read
+        let (mut old, mut new) = (0, 0);
+        let mut open = false;
+        diff.print(DiffFormat::Patch, |delta, hunk, line| {
+            if let Some(p) = delta.old_file().path().or_else(|| delta.new_file().path()) {
+                let segments = map.entry(p.to_string_lossy().to_string()).or_default();
+                let lineno = |n: Option<u32>| n.map_or(0, |n| usize::try_from(n).unwrap_or(0));
+                match line.origin() {
+                    'H' => {
+                        open = false;
+                        if let Some(h) = hunk {
+                            old = usize::try_from(h.old_start()).unwrap_or(0).max(1);
+                            new = usize::try_from(h.new_start()).unwrap_or(0).max(1);
+                        }
+                    }
+                    origin @ ('-' | '+') => {
+                        if !open {
+                            segments.push(Segment {
+                                old_start: old,
+                                new_start: new,
+                                ..Default::default()
+                            });
+                            open = true;
+                        }
+                        if let Some(s) = segments.last_mut() {
+                            if origin == '-' {
+                                s.old_lines += 1;
+                                old = lineno(line.old_lineno()) + 1;
+                            } else {
+                                s.new_lines += 1;
+                                new = lineno(line.new_lineno()) + 1;
+                            }
+                        }
+                    }
+                    ' ' => {
+                        open = false;
+                        old = lineno(line.old_lineno()) + 1;
+                        new = lineno(line.new_lineno()) + 1;
+                    }
+                    _ => {}
+                }
+            }
+            true
+        })
+        .ok();
+        LineMap(map)
+    }
+
+    // the line after the diff of an unchanged line before it, `Err` with the segment of a
+    // removed or changed line
+    fn line(&self, file: &str, line: usize) -> Result<usize, Segment> {
+        let mut mapped = line;
+        for s in self.0.get(file).into_iter().flatten() {
+            if line < s.old_start {
+                break;
+            }
+            if line < s.old_start + s.old_lines {
+                return Err(*s);
+            }
+            mapped = line + (s.new_start + s.new_lines) - (s.old_start + s.old_lines);
+        }
+        Ok(mapped)
+    }
+
+    // the lines after the diff of a span before it, widened to the replacement of its changed
+    // first and last lines
+    pub fn span(&self, file: &str, start_line: usize, end_line: usize) -> (usize, usize) {
+        let start = self.line(file, start_line).unwrap_or_else(|s| s.new_start);
+        let end = self
+            .line(file, end_line)
+            .unwrap_or_else(|s| (s.new_start + s.new_lines).saturating_sub(1));
+        (start, end.max(start))
+    }
+}
+
+fn raw_diff<'r>(
+    repo: &'r Repository,
+    from: &Revision,
+    to: &Revision,
+) -> Result<Diff<'r>, git2::Error> {
+    let mut opts = DiffOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .show_untracked_content(true);
+    match (from, to) {
+        (Revision::Tree(a), Revision::Tree(b)) => repo.diff_tree_to_tree(
+            Some(&repo.find_tree(*a)?),
+            Some(&repo.find_tree(*b)?),
+            Some(&mut opts),
+        ),
+        (Revision::Tree(a), Revision::WorkTree) => {
+            repo.diff_tree_to_workdir_with_index(Some(&repo.find_tree(*a)?), Some(&mut opts))
+        }
+        (Revision::WorkTree, Revision::Tree(b)) => repo
+            .diff_tree_to_workdir_with_index(Some(&repo.find_tree(*b)?), Some(opts.reverse(true))),
+        (Revision::WorkTree, Revision::WorkTree) => repo.diff_tree_to_tree(None, None, None),
+    }
+}
+
+// the work tree is dirty when a tracked file has been modified, staged, renamed or deleted;
+// untracked files are never touched and therefore do not count
+pub fn is_dirty(repo: &Repository) -> bool {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(false).include_ignored(false);
+    match repo.statuses(Some(&mut opts)) {
+        Ok(statuses) => !statuses.is_empty(),
+        Err(_) => true,
+    }
+}
+
+static SNAPSHOTS: AtomicUsize = AtomicUsize::new(0);
+
+// A revision exported into a temporary folder, so that it can be diagnosed without touching
+// the work tree, the index or the HEAD of the user's checkout. The folder is removed again
+// when the snapshot is dropped.
+pub struct Snapshot {
+    pub dir: PathBuf,
+    target_dir: PathBuf,
+    // the toolchain declared by the revision if it is installed, or else the current one
+    pub toolchain: Option<Toolchain>,
+}
+
+impl Snapshot {
+    // run the equivalent of the following bash commands
+    // ```bash
+    // mkdir -p $tmp
+    // git archive $tree_id | tar -x -C $tmp
+    // ```
+    // or copy the tracked and untracked (but not ignored) files of the work tree into `$tmp`
+    pub fn export(repo: &Repository, revision: &Revision) -> Result<Snapshot, git2::Error> {
+        let workdir = repo
+            .workdir()
+            .ok_or_else(|| git2::Error::from_str("cannot export from a bare repository"))?;
+        let dir = std::env::temp_dir().join(format!(
+            "rust-diagnostics-{}-{}",
+            std::process::id(),
+            SNAPSHOTS.fetch_add(1, Ordering::SeqCst)
+        ));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir).ok();
+        }
+        std::fs::create_dir_all(&dir).map_err(|e| git2::Error::from_str(&e.to_string()))?;
+        // created before the checkout so that a failed export is still cleaned up
+        let mut snapshot = Snapshot {
+            dir,
+            // separate from the user's own builds, but shared by all snapshots to reuse the
+            // compiled dependencies
+            target_dir: workdir.join("target").join("rust-diagnostics"),
+            toolchain: None,
+        };
+        match revision {
+            Revision::Tree(id) => {
+                let tree = repo.find_tree(*id)?;
+                repo.checkout_tree(
+                    tree.as_object(),
+                    Some(
+                        git2::build::CheckoutBuilder::new()
+                            .target_dir(&snapshot.dir)
+                            .update_index(false)
+                            .recreate_missing(true)
+                            .force(),
+                    ),
+                )?;
+            }
+            Revision::WorkTree => copy_work_tree(repo, workdir, &snapshot.dir)?,
+        }
+        // resolve the same dependency versions as the work tree when the revision has no lock
+        let lock = snapshot.dir.join("Cargo.lock");
+        if !lock.exists() && workdir.join("Cargo.lock").exists() {
+            std::fs::copy(workdir.join("Cargo.lock"), lock).ok();
+        }
+        snapshot.toolchain = toolchain::select(&snapshot.dir);
+        Ok(snapshot)
+    }
+
+    pub fn diagnose(
+        &self,
+        flags: Vec<String>,
+        retry_check: bool,
+    ) -> (HashMap<String, Vec<Ran>>, Build) {
+        let toolchain = self.toolchain.as_ref().map(|t| t.name.as_str());
+        // the artifacts of different toolchains are not compatible with each other
+        let target_dir = match toolchain {
+            Some(t) => self.target_dir.join(t),
+            None => self.target_dir.clone(),
+        };
+        diagnose_all_warnings(flags, &self.dir, Some(&target_dir), toolchain, retry_check)
+    }
+}
+
+impl Drop for Snapshot {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+fn copy_work_tree(repo: &Repository, workdir: &Path, dir: &Path) -> Result<(), git2::Error> {
+    let mut opts = StatusOptions::new();
+    opts.include_untracked(true)
+        .recurse_untracked_dirs(true)
+        .include_unmodified(true)
+        .include_ignored(false);
+    for entry in repo.statuses(Some(&mut opts))?.iter() {
+        if let Some(path) = entry.path() {
+            let source = workdir.join(path);
+            if source.is_file() {
+                let target = dir.join(path);
+                if let Some(p) = target.parent() {
+                    std::fs::create_dir_all(p).ok();
+                }
+                std::fs::copy(&source, &target)
+                    .map_err(|e| git2::Error::from_str(&e.to_string()))?;
+            }
+        }
+    }
+    Ok(())
+}
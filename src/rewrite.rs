@@ -0,0 +1,600 @@
+use tree_sitter::Node;
+use tree_sitter_parsers::parse;
+
+use crate::Ran;
+
+// The wrapper of the value which an `unwrap` panics on, as told by the note of the warning,
+// e.g. "if this value is an `Err`, it will panic"
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Wrapper {
+    Result,
+    Option,
+}
+
+impl Wrapper {
+    fn from_note(note: &str) -> Option<Wrapper> {
+        if note.contains("is an `Err`") {
+            Some(Wrapper::Result)
+        } else if note.contains("is `None`") {
+            Some(Wrapper::Option)
+        } else {
+            None
+        }
+    }
+
+    // the variant of the value that is unwrapped
+    fn variant(&self) -> &str {
+        match self {
+            Wrapper::Result => "Ok",
+            Wrapper::Option => "Some",
+        }
+    }
+
+    // the type a function must return for `?` to propagate the other variant
+    fn returned(&self) -> &str {
+        match self {
+            Wrapper::Result => "Result",
+            Wrapper::Option => "Option",
+        }
+    }
+}
+
+fn text<'a>(node: &Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+// the node spanning exactly the bytes, e.g. the call of `unwrap` a warning is reported on
+fn spanning<'a>(root: Node<'a>, start: usize, end: usize, kind: &str) -> Option<Node<'a>> {
+    let mut node = root.descendant_for_byte_range(start, end);
+    while let Some(n) = node {
+        if n.kind() == kind && (n.start_byte(), n.end_byte()) == (start, end) {
+            return Some(n);
+        }
+        if n.start_byte() < start || end < n.end_byte() {
+            return None;
+        }
+        node = n.parent();
+    }
+    None
+}
+
+// the function enclosing a node, unless a closure is nearer
+pub fn function(node: Node) -> Option<Node> {
+    let mut ancestor = node.parent();
+    while let Some(a) = ancestor {
+        match a.kind() {
+            "function_item" => return Some(a),
+            "closure_expression" => return None,
+            _ => ancestor = a.parent(),
+        }
+    }
+    None
+}
+
+// the name of a type without its path and generic arguments, e.g. `Result` for
+// `std::io::Result<String>`
+fn base(ty: &str) -> &str {
+    let ty = ty.split('<').next().unwrap_or(ty);
+    ty.rsplit("::").next().unwrap_or(ty).trim()
+}
+
+// whether a node of a block is a statement, rather than the tail expression of its value; a
+// macro invocation such as `println!(..);` is followed by its semicolon
+fn is_statement(node: &Node) -> bool {
+    let kind = node.kind();
+    kind.ends_with("_statement")
+        || kind.ends_with("_declaration")
+        || kind.ends_with("_item")
+        || kind.ends_with("_comment")
+        || node.next_sibling().map(|n| n.kind()) == Some(";")
+}
+
+// the whitespace before the node on its line
+fn indentation<'a>(node: &Node, source: &'a str) -> &'a str {
+    let line = source[..node.start_byte()].rfind('\n').map_or(0, |i| i + 1);
+    let prefix = &source[line..node.start_byte()];
+    if prefix.trim().is_empty() {
+        prefix
+    } else {
+        ""
+    }
+}
+
+// the bytes of a source to replace, and their replacement
+pub type Replacement = (usize, usize, String);
+
+// the name of the type a function returns, e.g. `Result`, or `None` when it returns nothing
+pub fn returned<'a>(function: Node, source: &'a str) -> Option<&'a str> {
+    function
+        .child_by_field_name("return_type")
+        .map(|t| base(text(&t, source)))
+}
+
+// Bind the pattern of the `let` statement of an expression to a variant of the value instead,
+// with `if let` around the rest of the block when it has no value, or with `let else`
+// returning from a function without a value. The `otherwise` statement, if any, is run when the
+// value is not of the variant.
+fn bind(
+    source: &str,
+    expression: Node,
+    (variant, value): (&str, &str),
+    returned: Option<&str>,
+    otherwise: Option<&str>,
+) -> Option<Replacement> {
+    let statement = expression.parent()?;
+    if statement.kind() != "let_declaration"
+        || statement.child_by_field_name("value") != Some(expression)
+        || statement.child_by_field_name("type").is_some()
+        || statement.child_by_field_name("alternative").is_some()
+    {
+        return None;
+    }
+    let pattern = text(&statement.child_by_field_name("pattern")?, source);
+    let indent = indentation(&statement, source);
+    let block = statement.parent()?;
+    let mut cursor = block.walk();
+    let rest: Vec<Node> = block
+        .named_children(&mut cursor)
+        .filter(|n| n.start_byte() >= statement.end_byte())
+        .collect();
+    if block.kind() == "block" && rest.iter().all(is_statement) {
+        // up to the semicolon of the last statement, before the closing brace of the block
+        let mut cursor = block.walk();
+        let end = block
+            .children(&mut cursor)
+            .filter(|n| n.kind() != "}")
+            .map(|n| n.end_byte())
+            .max()
+            .unwrap_or(statement.end_byte());
+        let body = source[statement.end_byte()..end]
+            .split('\n')
+            .enumerate()
+            .map(|(i, line)| {
+                if i == 0 || line.is_empty() {
+                    line.to_string()
+                } else {
+                    format!("    {line}")
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n");
+        let otherwise = otherwise.map_or(String::new(), |o| {
+            format!(" else {{\n{indent}    {o}\n{indent}}}")
+        });
+        return Some((
+            statement.start_byte(),
+            end,
+            format!("if let {variant}({pattern}) = {value} {{{body}\n{indent}}}{otherwise}"),
+        ));
+    }
+    if returned.is_none() {
+        return Some((
+            statement.start_byte(),
+            statement.end_byte(),
+            format!(
+                "let {variant}({pattern}) = {value} else {{\n{}{indent}    return;\n{indent}}};",
+                otherwise.map_or(String::new(), |o| format!("{indent}    {o}\n"))
+            ),
+        ));
+    }
+    None
+}
+
+// Rewrite the `unwrap` of a warning by propagating the other variant with `?` when the
+// enclosing function returns the same wrapper, otherwise by binding the unwrapped variant
+fn unwrap(source: &str, w: &Ran) -> Option<Replacement> {
+    let wrapper = Wrapper::from_note(&w.note)?;
+    let tree = parse(source, "rust");
+    let call = spanning(tree.root_node(), w.start, w.end, "call_expression")?;
+    let callee = call.child_by_field_name("function")?;
+    let arguments = call.child_by_field_name("arguments")?;
+    if callee.kind() != "field_expression"
+        || callee
+            .child_by_field_name("field")
+            .map(|f| text(&f, source))
+            != Some("unwrap")
+        || arguments.named_child_count() > 0
+    {
+        return None;
+    }
+    let value = text(&callee.child_by_field_name("value")?, source);
+    let returned = returned(function(call)?, source);
+    if returned == Some(wrapper.returned()) {
+        return Some((call.start_byte(), call.end_byte(), format!("{value}?")));
+    }
+    bind(source, call, (wrapper.variant(), value), returned, None)
+}
+
+// the `as` cast of a warning to a primitive numeric type, by its value and its type
+fn cast<'a>(root: Node<'a>, source: &'a str, w: &Ran) -> Option<(Node<'a>, &'a str, &'a str)> {
+    let cast = spanning(root, w.start, w.end, "type_cast_expression")?;
+    let ty = cast.child_by_field_name("type")?;
+    if ty.kind() != "primitive_type" || ["bool", "char", "str"].contains(&text(&ty, source)) {
+        return None;
+    }
+    let mut value = cast.child_by_field_name("value")?;
+    // `(a + b) as T` becomes `T::from(a + b)`
+    if value.kind() == "parenthesized_expression" {
+        value = value.named_child(0)?;
+    }
+    Some((cast, text(&value, source), text(&ty, source)))
+}
+
+// Rewrite a lossless cast `x as T` into `T::from(x)`
+fn from(source: &str, w: &Ran) -> Option<Replacement> {
+    let tree = parse(source, "rust");
+    let (cast, value, ty) = cast(tree.root_node(), source, w)?;
+    Some((
+        cast.start_byte(),
+        cast.end_byte(),
+        format!("{ty}::from({value})"),
+    ))
+}
+
+const INTEGERS: [&str; 12] = [
+    "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize",
+];
+
+// the last parameter or `let` of the function of a node which binds a variable before it
+fn binding<'a>(node: Node<'a>, source: &str, name: &str) -> Option<Node<'a>> {
+    let function = function(node)?;
+    let mut binding: Option<Node> = None;
+    let mut stack = vec![function];
+    while let Some(n) = stack.pop() {
+        if n.start_byte() >= node.start_byte() {
+            continue;
+        }
+        if ["parameter", "let_declaration"].contains(&n.kind())
+            && n.end_byte() <= node.start_byte()
+            && n.child_by_field_name("pattern").map(|p| text(&p, source)) == Some(name)
+            && binding.is_none_or(|b| b.start_byte() < n.start_byte())
+        {
+            binding = Some(n);
+        }
+        stack.extend(n.named_children(&mut n.walk()));
+    }
+    binding
+}
+
+// the type declared for a variable before a node, e.g. `u64` for `x` after `let x: u64 = 42;`
+fn declared<'a>(node: Node<'a>, source: &'a str, name: &str) -> Option<&'a str> {
+    let ty = binding(node, source, name)?.child_by_field_name("type")?;
+    Some(text(&ty, source))
+}
+
+// the type of the value of a cast, as told by the message of the warning, e.g. "casting `u64`
+// to `u32` may truncate the value", otherwise as declared for the variable which is cast
+fn source_type<'a>(cast: Node<'a>, source: &'a str, w: &'a Ran) -> Option<&'a str> {
+    let message = w.rendered.lines().next().unwrap_or("");
+    if let Some((_, rest)) = message.split_once("casting `") {
+        return rest.split_once('`').map(|(ty, _)| ty);
+    }
+    let value = cast.child_by_field_name("value")?;
+    if value.kind() != "identifier" {
+        return None;
+    }
+    declared(cast, source, text(&value, source))
+}
+
+// Rewrite a cast `x as T` between integers which may truncate, lose the sign or wrap into
+// `T::try_from(x)`, propagating the error with `?` when the enclosing function returns a
+// `Result`, otherwise by binding the converted value. Casts from or to floats are left as they
+// are, since there is no `TryFrom` between them and integers.
+fn try_from(source: &str, w: &Ran) -> Option<Replacement> {
+    let tree = parse(source, "rust");
+    let (cast, value, ty) = cast(tree.root_node(), source, w)?;
+    if !INTEGERS.contains(&ty) || !INTEGERS.contains(&source_type(cast, source, w)?) {
+        return None;
+    }
+    let converted = format!("{ty}::try_from({value})");
+    let returned = returned(function(cast)?, source);
+    if returned == Some("Result") {
+        return Some((cast.start_byte(), cast.end_byte(), format!("{converted}?")));
+    }
+    bind(source, cast, ("Ok", &converted), returned, None)
+}
+
+// the text of a string literal, when it can be put into a format string as it is
+fn literal<'a>(node: &Node, source: &'a str) -> Option<&'a str> {
+    let text = text(node, source);
+    let content = text.strip_prefix('"')?.strip_suffix('"')?;
+    if node.kind() != "string_literal" || content.contains(['{', '}']) {
+        return None;
+    }
+    Some(content)
+}
+
+// Rewrite the `expect` of a warning by propagating the other variant with `?`, keeping its
+// message as the context of the error, when the enclosing function returns a `Result` or the
+// same wrapper; otherwise by binding the expected variant and printing the message when it
+// is missing
+fn expect(source: &str, w: &Ran) -> Option<Replacement> {
+    let wrapper = Wrapper::from_note(&w.note)?;
+    let tree = parse(source, "rust");
+    let call = spanning(tree.root_node(), w.start, w.end, "call_expression")?;
+    let callee = call.child_by_field_name("function")?;
+    let arguments = call.child_by_field_name("arguments")?;
+    if callee.kind() != "field_expression"
+        || callee
+            .child_by_field_name("field")
+            .map(|f| text(&f, source))
+            != Some("expect")
+        || arguments.named_child_count() != 1
+    {
+        return None;
+    }
+    let value = text(&callee.child_by_field_name("value")?, source);
+    let argument = arguments.named_child(0)?;
+    let message = text(&argument, source);
+    let literal = literal(&argument, source);
+    let returned = returned(function(call)?, source);
+    let propagated = match (wrapper, returned) {
+        (Wrapper::Option, Some("Option")) => Some(format!("{value}?")),
+        (Wrapper::Option, Some("Result")) => Some(format!("{value}.ok_or({message})?")),
+        (Wrapper::Result, Some("Result")) => Some(match literal {
+            Some(m) => format!("{value}.map_err(|e| format!(\"{m}: {{e}}\"))?"),
+            None => format!("{value}.map_err(|e| format!(\"{{}}: {{e}}\", {message}))?"),
+        }),
+        _ => None,
+    };
+    if let Some(propagated) = propagated {
+        return Some((call.start_byte(), call.end_byte(), propagated));
+    }
+    let printed = match literal {
+        Some(m) => format!("eprintln!(\"{m}\");"),
+        None => format!("eprintln!(\"{{}}\", {message});"),
+    };
+    bind(
+        source,
+        call,
+        (wrapper.variant(), value),
+        returned,
+        Some(&printed),
+    )
+}
+
+// the arguments of a macro invocation, split at their top-level commas
+fn arguments<'a>(invocation: &Node, source: &'a str) -> Option<Vec<&'a str>> {
+    let tokens = invocation
+        .named_children(&mut invocation.walk())
+        .find(|n| n.kind() == "token_tree")?;
+    let (start, end) = (tokens.start_byte() + 1, tokens.end_byte() - 1);
+    let mut cursor = tokens.walk();
+    let mut commas: Vec<usize> = tokens
+        .children(&mut cursor)
+        .filter(|n| n.kind() == ",")
+        .map(|n| n.start_byte())
+        .collect();
+    commas.push(end);
+    let mut arguments = Vec::new();
+    let mut from = start;
+    for comma in commas {
+        let argument = source[from..comma].trim();
+        if !argument.is_empty() {
+            arguments.push(argument);
+        }
+        from = comma + 1;
+    }
+    Some(arguments)
+}
+
+// Rewrite the `dbg!` of a warning into the value of its arguments, or remove it as a statement
+// when they have no side effects, e.g. `dbg!(&x);`
+fn dbg(source: &str, w: &Ran) -> Option<Replacement> {
+    let tree = parse(source, "rust");
+    let invocation = spanning(tree.root_node(), w.start, w.end, "macro_invocation")?;
+    if invocation
+        .child_by_field_name("macro")
+        .map(|m| text(&m, source))
+        != Some("dbg")
+    {
+        return None;
+    }
+    let arguments = arguments(&invocation, source)?;
+    // a statement of its own, either wrapped in an `expression_statement` or followed by its
+    // semicolon in a block, but not the value of a `let` which is followed by the semicolon too
+    let parent = invocation.parent()?;
+    let semicolon = match parent.kind() {
+        "expression_statement" => Some(parent),
+        "block" => invocation.next_sibling().filter(|n| n.kind() == ";"),
+        _ => None,
+    };
+    if let Some(semicolon) = semicolon {
+        let pure = arguments.iter().all(|a| {
+            a.chars()
+                .all(|c| c.is_alphanumeric() || ['_', '.', '&', ' '].contains(&c))
+        });
+        if pure {
+            // the whole line, when the statement is alone on it
+            let indent = indentation(&invocation, source);
+            let start = invocation.start_byte() - indent.len();
+            let end = semicolon.end_byte();
+            let end = if source[end..].starts_with('\n') && !indent.is_empty() {
+                end + 1
+            } else {
+                end
+            };
+            return Some((start, end, String::new()));
+        }
+    }
+    let value = match arguments.as_slice() {
+        [] => "()".to_string(),
+        [argument] => argument.to_string(),
+        _ => format!("({})", arguments.join(", ")),
+    };
+    Some((invocation.start_byte(), invocation.end_byte(), value))
+}
+
+// an operand of an arithmetic expression, in parentheses unless it binds tighter
+fn operand(node: &Node, source: &str) -> String {
+    let text = text(node, source);
+    match node.kind() {
+        "identifier"
+        | "field_expression"
+        | "call_expression"
+        | "float_literal"
+        | "integer_literal"
+        | "parenthesized_expression"
+        | "index_expression" => text.to_string(),
+        _ => format!("({text})"),
+    }
+}
+
+// The type of a float expression: an unsuffixed literal such as `0.3` takes the type of the
+// other operand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Float {
+    Typed(&'static str),
+    Untyped,
+    Unknown,
+}
+
+impl Float {
+    fn of(ty: &str) -> Float {
+        match ty {
+            "f32" => Float::Typed("f32"),
+            "f64" => Float::Typed("f64"),
+            _ => Float::Unknown,
+        }
+    }
+
+    // the type of an arithmetic expression of both operands
+    fn and(self, other: Float) -> Float {
+        match (self, other) {
+            (Float::Typed(a), Float::Typed(b)) if a == b => self,
+            (Float::Typed(_), Float::Untyped) => self,
+            (Float::Untyped, Float::Typed(_)) => other,
+            (Float::Untyped, Float::Untyped) => Float::Untyped,
+            _ => Float::Unknown,
+        }
+    }
+}
+
+// the float type of an expression in the text, from the suffixes of its literals and the
+// declarations of its variables before the scope in the source, e.g. `f32` for `a` after
+// `let a: f32 = 0.1;` or `let a = 0.1_f32;`
+fn float(node: Node, text_of: &str, scope: Node, source: &str) -> Float {
+    match node.kind() {
+        "float_literal" => {
+            let literal = text(&node, text_of);
+            match literal.get(literal.len().saturating_sub(3)..) {
+                Some(suffix @ ("f32" | "f64")) => Float::of(suffix),
+                _ => Float::Untyped,
+            }
+        }
+        "identifier" => match binding(scope, source, text(&node, text_of)) {
+            Some(b) => match (
+                b.child_by_field_name("type"),
+                b.child_by_field_name("value"),
+            ) {
+                (Some(ty), _) => Float::of(text(&ty, source)),
+                (None, Some(value)) => float(value, source, value, source),
+                _ => Float::Unknown,
+            },
+            None => Float::Unknown,
+        },
+        "parenthesized_expression" | "unary_expression" => match node.named_child(0) {
+            Some(n) => float(n, text_of, scope, source),
+            None => Float::Unknown,
+        },
+        "binary_expression" => {
+            match (
+                node.child_by_field_name("left"),
+                node.child_by_field_name("right"),
+            ) {
+                (Some(l), Some(r)) => {
+                    float(l, text_of, scope, source).and(float(r, text_of, scope, source))
+                }
+                _ => Float::Unknown,
+            }
+        }
+        _ => Float::Unknown,
+    }
+}
+
+// Rewrite the float comparison `a == b` of a warning into `(a - b).abs() < f64::EPSILON`, and
+// `a != b` into its negation, with the margin of the type of the operands; it is left as it is
+// when their type is unknown. The comparison is parsed on its own, since it is often an
+// argument of a macro such as `println!`, which is not parsed into expressions.
+fn epsilon(source: &str, w: &Ran) -> Option<Replacement> {
+    const PREFIX: &str = "fn f() { ";
+    let snippet = format!("{PREFIX}{} }}", source.get(w.start..w.end)?);
+    let tree = parse(&snippet, "rust");
+    let end = snippet.len() - 2;
+    let comparison = spanning(tree.root_node(), PREFIX.len(), end, "binary_expression")?;
+    let left = comparison.child_by_field_name("left")?;
+    let right = comparison.child_by_field_name("right")?;
+    let comparator = match comparison
+        .child_by_field_name("operator")
+        .map(|o| text(&o, &snippet))
+    {
+        Some("==") => "<",
+        Some("!=") => ">=",
+        _ => return None,
+    };
+    let original = parse(source, "rust");
+    let scope = original
+        .root_node()
+        .descendant_for_byte_range(w.start, w.end)?;
+    let ty = match float(left, &snippet, scope, source).and(float(right, &snippet, scope, source)) {
+        Float::Typed(ty) => ty,
+        _ => return None,
+    };
+    Some((
+        w.start,
+        w.end,
+        format!(
+            "({} - {}).abs() {comparator} {ty}::EPSILON",
+            operand(&left, &snippet),
+            operand(&right, &snippet)
+        ),
+    ))
+}
+
+// Rewrite the warnings in the source, from the last one to the first one so that the offsets
+// of the others still hold, leaving those which cannot be rewritten as they are
+pub fn rewrite(
+    source: &str,
+    warnings: &[Ran],
+    f: impl Fn(&str, &Ran) -> Option<Replacement>,
+) -> String {
+    let mut warnings: Vec<&Ran> = warnings.iter().collect();
+    warnings.sort_by_key(|w| std::cmp::Reverse(w.start));
+    let mut output = source.to_string();
+    for w in warnings {
+        if let Some((start, end, replacement)) = f(&output, w) {
+            output.replace_range(start..end, &replacement);
+        }
+    }
+    output
+}
+
+// Rewrite the warnings of `clippy::unwrap_used`
+pub fn unwrap_used(source: &str, warnings: &[Ran]) -> String {
+    rewrite(source, warnings, unwrap)
+}
+
+// Rewrite the warnings of `clippy::expect_used`
+pub fn expect_used(source: &str, warnings: &[Ran]) -> String {
+    rewrite(source, warnings, expect)
+}
+
+// Rewrite the warnings of `clippy::dbg_macro`
+pub fn dbg_macro(source: &str, warnings: &[Ran]) -> String {
+    rewrite(source, warnings, dbg)
+}
+
+// Rewrite the warnings of `clippy::float_cmp`
+pub fn float_cmp(source: &str, warnings: &[Ran]) -> String {
+    rewrite(source, warnings, epsilon)
+}
+
+// Rewrite the warnings of `clippy::cast_lossless`
+pub fn cast_lossless(source: &str, warnings: &[Ran]) -> String {
+    rewrite(source, warnings, from)
+}
+
+// Rewrite the warnings of the casts which may not be lossless, e.g. `clippy::cast_sign_loss`
+pub fn fallible_casts(source: &str, warnings: &[Ran]) -> String {
+    rewrite(source, warnings, try_from)
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::pattern::{self, Pattern};
+use crate::rewrite;
+use crate::Ran;
+
+// the rules shipped with the tool, by their file names under `refactoring`
+const BUNDLED: [(&str, &str); 2] = [
+    (
+        "get_unwrap.ssr",
+        include_str!("../refactoring/get_unwrap.ssr"),
+    ),
+    (
+        "unwrap_used.txl",
+        include_str!("../refactoring/unwrap_used.txl"),
+    ),
+];
+
+// the rules rewriting the syntax trees of the sources natively, by the lints they transform
+const NATIVE: [(&str, &str, Native); 9] = [
+    ("clippy::unwrap_used", "unwrap_used", rewrite::unwrap_used),
+    ("clippy::expect_used", "expect_used", rewrite::expect_used),
+    ("clippy::dbg_macro", "dbg_macro", rewrite::dbg_macro),
+    ("clippy::float_cmp", "float_cmp", rewrite::float_cmp),
+    ("clippy::cast_lossless", "from", rewrite::cast_lossless),
+    (
+        "clippy::as_conversions",
+        "try_from",
+        rewrite::fallible_casts,
+    ),
+    (
+        "clippy::cast_possible_truncation",
+        "try_from",
+        rewrite::fallible_casts,
+    ),
+    (
+        "clippy::cast_sign_loss",
+        "try_from",
+        rewrite::fallible_casts,
+    ),
+    (
+        "clippy::cast_possible_wrap",
+        "try_from",
+        rewrite::fallible_casts,
+    ),
+];
+
+// A native rule rewrites a source by the warnings of its lint in it
+pub type Native = fn(&str, &[Ran]) -> String;
+
+// Where a rule has been loaded from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Native,
+    Bundled,
+    Local(PathBuf),
+}
+
+impl std::fmt::Display for Origin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Origin::Native => write!(f, "native"),
+            Origin::Bundled => write!(f, "bundled"),
+            Origin::Local(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+// How a rule transforms a source: natively, by structural search-and-replace patterns, or by
+// the text of a TXL program
+#[derive(Debug, Clone)]
+pub enum Transform {
+    Native(Native),
+    Pattern(Vec<Pattern>),
+    Txl(String),
+}
+
+impl Transform {
+    // the transformation of a rule by the extension of its file, e.g. `get_unwrap.ssr` for the
+    // patterns and `unwrap_used.txl` for TXL
+    fn new(name: &str, text: &str) -> std::io::Result<Transform> {
+        if name.ends_with(".ssr") {
+            pattern::parse(text).map(Transform::Pattern).map_err(|e| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, format!("{name}: {e}"))
+            })
+        } else {
+            Ok(Transform::Txl(text.to_string()))
+        }
+    }
+
+    // the order in which the rules of a lint are tried: the native ones, which know the most
+    // about the warnings, then the patterns and then TXL
+    fn rank(&self) -> u8 {
+        match self {
+            Transform::Native(_) => 0,
+            Transform::Pattern(_) => 1,
+            Transform::Txl(_) => 2,
+        }
+    }
+}
+
+// A rule, named by its file for the patterns and TXL, e.g. `get_unwrap.ssr` or
+// `unwrap_used.let_else.txl`
+#[derive(Debug, Clone)]
+pub struct Rule {
+    pub name: String,
+    pub transform: Transform,
+    pub origin: Origin,
+}
+
+// the lint which a rule transforms, from the part of its file name before the first dot, e.g.
+// `clippy::unwrap_used` for `unwrap_used.let_else.txl`
+fn lint(name: &str) -> Option<String> {
+    let stem = name
+        .strip_suffix(".txl")
+        .or_else(|| name.strip_suffix(".ssr"))?;
+    let lint = stem.split('.').next().unwrap_or(stem);
+    if lint.is_empty() {
+        None
+    } else {
+        Some(format!("clippy::{lint}"))
+    }
+}
+
+// The rules of the lints: the native ones, and the patterns and TXL ones which are either
+// embedded in the tool or read from a local folder; the rules of the folder replace the native
+// and bundled rules of their lint. Nothing is ever downloaded.
+#[derive(Debug, Default)]
+pub struct Registry {
+    rules: BTreeMap<String, Vec<Rule>>,
+}
+
+impl Registry {
+    pub fn new(dir: Option<&Path>) -> std::io::Result<Registry> {
+        let mut registry = Registry::default();
+        for (lint, name, native) in NATIVE {
+            registry
+                .rules
+                .entry(lint.to_string())
+                .or_default()
+                .push(Rule {
+                    name: name.to_string(),
+                    transform: Transform::Native(native),
+                    origin: Origin::Native,
+                });
+        }
+        for (name, text) in BUNDLED {
+            registry.insert(Rule {
+                name: name.to_string(),
+                transform: Transform::new(name, text)?,
+                origin: Origin::Bundled,
+            });
+        }
+        if let Some(dir) = dir {
+            let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)?
+                .flatten()
+                .map(|e| e.path())
+                .filter(|p| p.is_file())
+                .collect();
+            paths.sort();
+            for path in paths {
+                let Some(name) = path.file_name().map(|n| n.to_string_lossy().to_string()) else {
+                    continue;
+                };
+                if lint(&name).is_some() {
+                    registry.insert(Rule {
+                        transform: Transform::new(&name, &std::fs::read_to_string(&path)?)?,
+                        name,
+                        origin: Origin::Local(path),
+                    });
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    fn insert(&mut self, rule: Rule) {
+        if let Some(lint) = lint(&rule.name) {
+            let rules = self.rules.entry(lint).or_default();
+            rules.retain(|r| r.name != rule.name);
+            if matches!(rule.origin, Origin::Local(_)) {
+                rules.retain(|r| matches!(r.origin, Origin::Local(_)));
+            }
+            rules.push(rule);
+            // the native rules first, then the patterns and the TXL ones in the order of
+            // their names
+            rules.sort_by(|a, b| (a.transform.rank(), &a.name).cmp(&(b.transform.rank(), &b.name)));
+        }
+    }
+
+    // the rules of a lint, the native ones first
+    pub fn rules(&self, lint: &str) -> &[Rule] {
+        self.rules.get(lint).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    pub fn print_table(&self) {
+        println!("{:<40} {:<40} origin", "lint", "rule");
+        for (lint, rules) in &self.rules {
+            for r in rules {
+                println!("{:<40} {:<40} {}", lint, r.name, r.origin);
+            }
+        }
+    }
+}
+
+// whether the distribution of TXL has been unpacked in the folder, e.g. as
+// `txl10.8b.linux64/bin/txl`, so that `txl_rs` does not download it
+pub fn installed(cache: &Path) -> bool {
+    std::fs::read_dir(cache).is_ok_and(|entries| {
+        entries.flatten().any(|e| {
+            let bin = e.path().join("bin");
+            bin.join("txl").is_file() || bin.join("txl.exe").is_file()
+        })
+    })
+}
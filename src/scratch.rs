@@ -0,0 +1,66 @@
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+static SCRATCHES: AtomicUsize = AtomicUsize::new(0);
+
+// the top-level folders that are neither needed nor touched by the transformations
+const SKIPPED: [&str; 5] = [".git", "target", "diagnostics", "transform", "original"];
+
+// A copy of the workspace in a temporary folder, where the transformations may rewrite the
+// sources at will: the user's files are only ever read. The folder is removed again when the
+// copy is dropped, including by a panic.
+pub struct Scratch {
+    pub dir: PathBuf,
+}
+
+impl Scratch {
+    // run the equivalent of the following bash commands
+    // ```bash
+    // mkdir -p $tmp
+    // rsync -a --exclude .git --exclude target $workspace/ $tmp/
+    // ```
+    pub fn copy(workspace: &Path) -> std::io::Result<Scratch> {
+        let dir = std::env::temp_dir().join(format!(
+            "rust-diagnostics-fix-{}-{}",
+            std::process::id(),
+            SCRATCHES.fetch_add(1, Ordering::SeqCst)
+        ));
+        if dir.exists() {
+            std::fs::remove_dir_all(&dir)?;
+        }
+        std::fs::create_dir_all(&dir)?;
+        let scratch = Scratch { dir };
+        copy_dir(workspace, &scratch.dir, &SKIPPED)?;
+        Ok(scratch)
+    }
+
+    // the copy of a file of the workspace
+    pub fn path(&self, file: &str) -> PathBuf {
+        self.dir.join(file)
+    }
+}
+
+impl Drop for Scratch {
+    fn drop(&mut self) {
+        std::fs::remove_dir_all(&self.dir).ok();
+    }
+}
+
+fn copy_dir(from: &Path, to: &Path, skipped: &[&str]) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        if skipped.iter().any(|s| name == *s) {
+            continue;
+        }
+        let source = entry.path();
+        let target = to.join(&name);
+        if source.is_dir() {
+            std::fs::create_dir_all(&target)?;
+            copy_dir(&source, &target, &[])?;
+        } else {
+            std::fs::copy(&source, &target)?;
+        }
+    }
+    Ok(())
+}
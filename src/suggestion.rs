@@ -0,0 +1,256 @@
+use std::collections::HashSet;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use cargo_metadata::diagnostic::{Applicability, Diagnostic, DiagnosticSpan};
+use cargo_metadata::Message;
+use git2::Patch;
+
+use crate::Ran;
+
+// the applicabilities of the suggestions, from the safest to the least safe
+pub const APPLICABILITIES: [&str; 4] = [
+    "machine-applicable",
+    "maybe-incorrect",
+    "has-placeholders",
+    "unspecified",
+];
+
+// the rank of an applicability in `APPLICABILITIES`
+fn rank(applicability: Option<&Applicability>) -> usize {
+    match applicability {
+        Some(Applicability::MachineApplicable) => 0,
+        Some(Applicability::MaybeIncorrect) => 1,
+        Some(Applicability::HasPlaceholders) => 2,
+        _ => 3,
+    }
+}
+
+// the rank of the least safe applicability accepted, by its name in `APPLICABILITIES`
+pub fn threshold(name: &str) -> usize {
+    APPLICABILITIES.iter().position(|a| *a == name).unwrap_or(0)
+}
+
+// A replacement of the bytes of a file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edit {
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub replacement: String,
+}
+
+impl Edit {
+    // whether two edits touch the same bytes, or insert at the same place in an undefined order
+    fn overlaps(&self, other: &Edit) -> bool {
+        self.file == other.file
+            && (self.start < other.end && other.start < self.end
+                || self.start == other.start
+                    && (self.start == self.end || other.start == other.end))
+    }
+}
+
+// A suggestion of a diagnostic, which is located by its primary span: the edits of its parts,
+// which are applied all together or not at all, and the rank of the least safe of them
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub lint: String,
+    pub file: String,
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub message: String,
+    pub applicability: usize,
+    pub edits: Vec<Edit>,
+}
+
+fn edit(s: &DiagnosticSpan) -> Option<Edit> {
+    Some(Edit {
+        file: s.file_name.clone(),
+        start: usize::try_from(s.byte_start).ok()?,
+        end: usize::try_from(s.byte_end).ok()?,
+        replacement: s.suggested_replacement.clone()?,
+    })
+}
+
+// the suggestions of a diagnostic: its own suggested replacements, and those of each of its
+// children, which are alternatives to each other
+fn collect(diagnostic: &Diagnostic, primary: &Suggestion, suggestions: &mut Vec<Suggestion>) {
+    let parts: Vec<&DiagnosticSpan> = diagnostic
+        .spans
+        .iter()
+        .filter(|s| s.suggested_replacement.is_some())
+        .collect();
+    let edits: Vec<Edit> = parts.iter().filter_map(|s| edit(s)).collect();
+    if !edits.is_empty() && edits.len() == parts.len() {
+        suggestions.push(Suggestion {
+            message: diagnostic.message.clone(),
+            applicability: parts
+                .iter()
+                .map(|s| rank(s.suggestion_applicability.as_ref()))
+                .max()
+                .unwrap_or(0),
+            edits,
+            ..primary.clone()
+        });
+    }
+    for c in &diagnostic.children {
+        collect(c, primary, suggestions);
+    }
+}
+
+// Run clippy with the flags in the folder, and collect the suggestions of its diagnostics
+pub fn suggestions(dir: &Path, flags: &[String]) -> Vec<Suggestion> {
+    let mut suggestions = Vec::new();
+    let Ok(mut command) = Command::new("cargo")
+        .args(["clippy", "--message-format=json", "--"])
+        .args(flags)
+        .current_dir(dir)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    else {
+        return suggestions;
+    };
+    if let Some(take) = command.stdout.take() {
+        let reader = std::io::BufReader::new(take);
+        for message in Message::parse_stream(reader).flatten() {
+            if let Message::CompilerMessage(msg) = message {
+                let diagnostic = msg.message;
+                let (Some(code), Some(primary)) = (
+                    &diagnostic.code,
+                    diagnostic.spans.iter().find(|s| s.is_primary),
+                ) else {
+                    continue;
+                };
+                if let (Ok(start), Ok(end)) = (
+                    usize::try_from(primary.byte_start),
+                    usize::try_from(primary.byte_end),
+                ) {
+                    let primary = Suggestion {
+                        lint: code.code.clone(),
+                        file: primary.file_name.clone(),
+                        start,
+                        end,
+                        line: primary.line_start,
+                        message: String::new(),
+                        applicability: 0,
+                        edits: Vec::new(),
+                    };
+                    collect(&diagnostic, &primary, &mut suggestions);
+                }
+            }
+        }
+    }
+    command.wait().ok();
+    suggestions
+}
+
+// The suggestions chosen to be applied to a file, and those skipped because their edits
+// overlap the edits of the chosen ones
+#[derive(Debug, Default)]
+pub struct Plan<'a> {
+    pub accepted: Vec<&'a Suggestion>,
+    pub conflicts: Vec<&'a Suggestion>,
+}
+
+impl Plan<'_> {
+    // whether the warning is fixed by one of the chosen suggestions
+    pub fn fixes(&self, w: &Ran) -> bool {
+        self.accepted
+            .iter()
+            .any(|s| (s.start, s.end) == (w.start, w.end) && s.lint == w.lint())
+    }
+}
+
+// Choose the suggestions to apply to a file: for each diagnostic, the first of its suggestions
+// which is at most as unsafe as the threshold and whose edits overlap neither each other nor
+// the edits of the suggestions chosen before it
+pub fn plan<'a>(
+    file: &str,
+    suggestions: impl Iterator<Item = &'a Suggestion>,
+    threshold: usize,
+) -> Plan<'a> {
+    let mut plan = Plan::default();
+    let mut done = HashSet::new();
+    for s in suggestions.filter(|s| s.file == file && s.applicability <= threshold) {
+        if done.contains(&(&s.lint, s.start, s.end)) {
+            continue;
+        }
+        let overlapping = s.edits.iter().enumerate().any(|(i, e)| {
+            s.edits[..i].iter().any(|f| e.overlaps(f))
+                || plan
+                    .accepted
+                    .iter()
+                    .any(|a| a.edits.iter().any(|f| e.overlaps(f)))
+        });
+        if overlapping {
+            plan.conflicts.push(s);
+        } else {
+            done.insert((&s.lint, s.start, s.end));
+            plan.accepted.push(s);
+        }
+    }
+    // an alternative suggestion of a diagnostic chosen after all is no conflict
+    plan.conflicts
+        .retain(|s| !done.contains(&(&s.lint, s.start, s.end)));
+    plan
+}
+
+// Apply the edits of the chosen suggestions to the source of the file, from the last one to
+// the first one so that the offsets of the others still hold
+pub fn apply(file: &str, source: &str, accepted: &[&Suggestion]) -> String {
+    let mut edits: Vec<&Edit> = accepted
+        .iter()
+        .flat_map(|s| &s.edits)
+        .filter(|e| e.file == file)
+        .collect();
+    edits.sort_by_key(|e| std::cmp::Reverse((e.start, e.end)));
+    let mut output = source.to_string();
+    for e in edits {
+        if e.start <= e.end && e.end <= output.len() {
+            output.replace_range(e.start..e.end, &e.replacement);
+        }
+    }
+    output
+}
+
+// Locate a warning not fixed by the chosen suggestions in the output, after the edits before it
+pub fn relocate(w: &Ran, plan: &Plan, output: &str) -> Ran {
+    let shift = |offset: usize| {
+        let mut shifted = offset;
+        for e in plan.accepted.iter().flat_map(|s| &s.edits) {
+            if e.end <= offset {
+                shifted = (shifted + e.replacement.len()).saturating_sub(e.end - e.start);
+            }
+        }
+        shifted.min(output.len())
+    };
+    let line = |offset: usize| {
+        1 + output.as_bytes()[..offset]
+            .iter()
+            .filter(|b| **b == b'\n')
+            .count()
+    };
+    let (start, end) = (shift(w.start), shift(w.end));
+    Ran {
+        start,
+        end,
+        start_line: line(start),
+        end_line: line(end),
+        ..w.clone()
+    }
+}
+
+// The unified diff of a file, as a preview of the suggestions applied to it
+pub fn preview(file: &str, input: &str, output: &str) -> Result<String, git2::Error> {
+    let mut patch = Patch::from_buffers(
+        input.as_bytes(),
+        Some(Path::new(file)),
+        output.as_bytes(),
+        Some(Path::new(file)),
+        None,
+    )?;
+    let buf = patch.to_buf()?;
+    Ok(String::from_utf8_lossy(&buf).to_string())
+}
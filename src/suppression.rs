@@ -0,0 +1,340 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use git2::{Diff, DiffDelta, DiffFormat, Repository};
+use serde::Serialize;
+use tree_sitter::{Node, Point};
+use tree_sitter_parsers::parse;
+
+use crate::Ran;
+
+// How a warning has been silenced instead of fixed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Suppression {
+    // an `#[allow]`/`#[expect]` attribute on an enclosing item, or `#![allow]` in a module
+    Attribute,
+    // an `#![allow]`/`#![expect]` attribute at the top of a crate root
+    Crate,
+    // a lint level set in `Cargo.toml`, `clippy.toml` or `.cargo/config`
+    Config,
+}
+
+// a lint newly allowed or expected for the lines of a file, or for every file of the crate
+struct Silenced {
+    file: Option<String>,
+    lint: String,
+    start_line: usize,
+    end_line: usize,
+    by: Suppression,
+}
+
+// The lints silenced by a diff, and where
+#[derive(Default)]
+pub struct Suppressions(Vec<Silenced>);
+
+const CONFIG_FILES: [&str; 5] = [
+    "Cargo.toml",
+    "clippy.toml",
+    ".clippy.toml",
+    ".cargo/config",
+    ".cargo/config.toml",
+];
+
+// the lines of a file as changed by a diff
+#[derive(Default)]
+struct FileChanges {
+    // the added lines, grouped by the line of the first revision they are inserted before
+    added: Vec<(usize, String)>,
+    removed: Vec<usize>,
+}
+
+impl Suppressions {
+    pub fn from_diff(repo: &Repository, diff: &Diff) -> Suppressions {
+        let mut files: HashMap<String, FileChanges> = HashMap::new();
+        let mut sources: HashMap<String, String> = HashMap::new();
+        let mut position = 0;
+        diff.print(DiffFormat::Patch, |delta, hunk, line| {
+            if let Some(p) = delta.old_file().path().or_else(|| delta.new_file().path()) {
+                let file = p.to_string_lossy().to_string();
+                if !sources.contains_key(&file) {
+                    sources.insert(file.clone(), old_source(repo, &delta));
+                }
+                let changes = files.entry(file).or_default();
+                match (line.origin(), line.old_lineno()) {
+                    ('H', _) => {
+                        position = hunk.map_or(0, |h| usize::try_from(h.old_start()).unwrap_or(0));
+                    }
+                    ('+', _) => {
+                        let content = String::from_utf8_lossy(line.content()).to_string();
+                        match changes.added.last_mut() {
+                            Some((p, chunk)) if *p == position => chunk.push_str(&content),
+                            _ => changes.added.push((position, content)),
+                        }
+                    }
+                    ('-', Some(n)) => {
+                        let n = usize::try_from(n).unwrap_or(0);
+                        changes.removed.push(n);
+                        position = n + 1;
+                    }
+                    (_, Some(n)) => position = usize::try_from(n).unwrap_or(0) + 1,
+                    _ => {}
+                }
+            }
+            true
+        })
+        .ok();
+        let mut silenced = Vec::new();
+        for (file, changes) in &files {
+            if CONFIG_FILES.iter().any(|c| file.ends_with(c)) {
+                for (_, chunk) in &changes.added {
+                    for lint in configured_lints(chunk) {
+                        silenced.push(Silenced {
+                            file: None,
+                            lint,
+                            start_line: 0,
+                            end_line: usize::MAX,
+                            by: Suppression::Config,
+                        });
+                    }
+                }
+            } else if file.ends_with(".rs") {
+                if let Some(source) = sources.get(file) {
+                    silenced.extend(silenced_by_attributes(file, source, changes));
+                }
+            }
+        }
+        Suppressions(silenced)
+    }
+
+    // how the warning of the file has been silenced, if at all
+    pub fn of(&self, file: &str, w: &Ran) -> Option<Suppression> {
+        self.0
+            .iter()
+            .find(|s| {
+                (s.lint == w.lint() || s.lint == "warnings")
+                    && s.file.as_ref().is_none_or(|f| f == file)
+                    && s.start_line <= w.start_line
+                    && w.end_line <= s.end_line
+            })
+            .map(|s| s.by)
+    }
+}
+
+// the content of a file before the change, from the object database or else the work tree
+fn old_source(repo: &Repository, delta: &DiffDelta) -> String {
+    let file = delta.old_file();
+    if let Ok(blob) = repo.find_blob(file.id()) {
+        return String::from_utf8_lossy(blob.content()).to_string();
+    }
+    match (repo.workdir(), file.path()) {
+        (Some(dir), Some(p)) => std::fs::read_to_string(dir.join(p)).unwrap_or_default(),
+        _ => String::new(),
+    }
+}
+
+// the clippy lints set to `allow` in a chunk of `Cargo.toml` (`unwrap_used = "allow"`), or
+// passed as `-A clippy::unwrap_used` through the rustflags of `.cargo/config`
+fn configured_lints(chunk: &str) -> Vec<String> {
+    let mut lints = Vec::new();
+    for line in chunk.lines() {
+        if let Some((key, value)) = line.split_once('=') {
+            if value.contains("allow") || value.contains("expect") {
+                let key = key.trim().trim_matches('"').replace('-', "_");
+                if !key.is_empty() && !key.contains(' ') {
+                    lints.push(format!("clippy::{}", key.trim_start_matches("clippy::")));
+                }
+            }
+        }
+        let words: Vec<&str> = line
+            .split(|c: char| c.is_whitespace() || c == '"' || c == ',' || c == '[' || c == ']')
+            .filter(|w| !w.is_empty())
+            .collect();
+        for pair in words.windows(2) {
+            if pair[0] == "-A" {
+                lints.push(pair[1].replace('-', "_"));
+            }
+        }
+        for w in &words {
+            if let Some(lint) = w.strip_prefix("-A") {
+                if !lint.is_empty() {
+                    lints.push(lint.replace('-', "_"));
+                }
+            }
+        }
+    }
+    lints
+}
+
+fn text<'a>(node: &Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+// the lints of an `allow` or `expect` attribute
+fn allowed_lints(attribute: &Node, source: &str) -> Vec<String> {
+    let mut lints = Vec::new();
+    let mut cursor = attribute.walk();
+    for meta in attribute.named_children(&mut cursor) {
+        if meta.kind() != "meta_item" {
+            continue;
+        }
+        let level = meta.named_child(0).map(|n| text(&n, source)).unwrap_or("");
+        if level != "allow" && level != "expect" {
+            continue;
+        }
+        if let Some(arguments) = meta.child_by_field_name("arguments") {
+            let mut cursor = arguments.walk();
+            for lint in arguments.named_children(&mut cursor) {
+                if lint.kind() == "meta_item" {
+                    lints.push(text(&lint, source).split_whitespace().collect());
+                }
+            }
+        }
+    }
+    lints
+}
+
+fn is_attribute(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        "attribute_item" | "inner_attribute_item" | "line_comment" | "block_comment"
+    )
+}
+
+// the first syntax node starting at or after the 1-based line, which an outer attribute
+// inserted before that line applies to
+fn first_node_from<'t>(root: Node<'t>, line: usize) -> Option<Node<'t>> {
+    let row = line.saturating_sub(1);
+    let mut cursor = root.walk();
+    let mut node = root;
+    loop {
+        if node.start_position().row >= row
+            && node.is_named()
+            && !is_attribute(&node)
+            && node != root
+        {
+            return Some(node);
+        }
+        let descend =
+            !is_attribute(&node) && node.end_position().row >= row && cursor.goto_first_child();
+        if !descend {
+            while !cursor.goto_next_sibling() {
+                if !cursor.goto_parent() {
+                    return None;
+                }
+            }
+        }
+        node = cursor.node();
+    }
+}
+
+// the lines of the module, function or block around the 1-based line, which an inner
+// attribute inserted before that line applies to; `None` stands for the whole file
+fn enclosing_lines(root: Node, line: usize) -> Option<(usize, usize)> {
+    let point = Point::new(line.saturating_sub(1), 0);
+    let mut node = root.descendant_for_point_range(point, point);
+    while let Some(n) = node {
+        match n.kind() {
+            "source_file" => return None,
+            "declaration_list" | "block" => {
+                let item = n.parent().unwrap_or(n);
+                return Some((item.start_position().row + 1, item.end_position().row + 1));
+            }
+            _ => node = n.parent(),
+        }
+    }
+    None
+}
+
+fn is_crate_root(file: &str) -> bool {
+    let path = Path::new(file);
+    let parent = path
+        .parent()
+        .and_then(|p| p.file_name())
+        .unwrap_or_default();
+    file == "build.rs"
+        || file.ends_with("src/lib.rs")
+        || file.ends_with("src/main.rs")
+        || ["bin", "examples", "tests", "benches"].contains(&parent.to_str().unwrap_or(""))
+}
+
+fn silenced_by_attributes(file: &str, source: &str, changes: &FileChanges) -> Vec<Silenced> {
+    let tree = parse(source, "rust");
+    let root = tree.root_node();
+    // the lints that were already allowed on an item, by the removed attributes before it
+    let mut previously: Vec<((usize, usize), String)> = Vec::new();
+    let mut cursor = root.walk();
+    let mut stack = vec![root];
+    while let Some(node) = stack.pop() {
+        if node.kind() == "attribute_item"
+            && changes.removed.contains(&(node.start_position().row + 1))
+        {
+            if let Some(target) = first_node_from(root, node.end_position().row + 2) {
+                let lines = (
+                    target.start_position().row + 1,
+                    target.end_position().row + 1,
+                );
+                for lint in allowed_lints(&node, source) {
+                    previously.push((lines, lint));
+                }
+            }
+        }
+        stack.extend(node.named_children(&mut cursor));
+    }
+    let mut silenced = Vec::new();
+    for (position, chunk) in &changes.added {
+        let added = parse(chunk, "rust");
+        let mut cursor = added.root_node().walk();
+        for attribute in added.root_node().named_children(&mut cursor) {
+            let inner = match attribute.kind() {
+                "attribute_item" => false,
+                "inner_attribute_item" => true,
+                _ => continue,
+            };
+            // the attribute of newly added code does not silence the warnings of old code
+            let mut next = attribute.next_named_sibling();
+            while next.is_some_and(|n| is_attribute(&n)) {
+                next = next.and_then(|n| n.next_named_sibling());
+            }
+            if !inner && next.is_some() {
+                continue;
+            }
+            let (lines, by) = if inner {
+                match enclosing_lines(root, *position) {
+                    Some(lines) => (Some(lines), Suppression::Attribute),
+                    None if is_crate_root(file) => (None, Suppression::Crate),
+                    None => (None, Suppression::Attribute),
+                }
+            } else {
+                match first_node_from(root, *position) {
+                    Some(target) => (
+                        Some((
+                            target.start_position().row + 1,
+                            target.end_position().row + 1,
+                        )),
+                        Suppression::Attribute,
+                    ),
+                    None => continue,
+                }
+            };
+            for lint in allowed_lints(&attribute, chunk) {
+                if lines.is_some_and(|l| previously.contains(&(l, lint.clone()))) {
+                    continue;
+                }
+                let (start_line, end_line) = lines.unwrap_or((0, usize::MAX));
+                silenced.push(Silenced {
+                    file: if by == Suppression::Crate {
+                        None
+                    } else {
+                        Some(file.to_string())
+                    },
+                    lint,
+                    start_line,
+                    end_line,
+                    by,
+                });
+            }
+        }
+    }
+    silenced
+}
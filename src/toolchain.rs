@@ -0,0 +1,81 @@
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use serde::Serialize;
+
+// The rust toolchain a revision is diagnosed with, and the version of its clippy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Toolchain {
+    pub name: String,
+    pub clippy: String,
+}
+
+fn output(command: &mut Command) -> Option<String> {
+    let output = command.stderr(Stdio::null()).output().ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        None
+    }
+}
+
+// the channel of the `rust-toolchain.toml` or `rust-toolchain` file of a folder, either as
+// `channel = "1.60.0"` under `[toolchain]` or, in the legacy format, as the only line
+pub fn declared(dir: &Path) -> Option<String> {
+    let content = ["rust-toolchain.toml", "rust-toolchain"]
+        .iter()
+        .find_map(|f| std::fs::read_to_string(dir.join(f)).ok())?;
+    let channel = content.lines().find_map(|line| {
+        let (key, value) = line.split_once('=')?;
+        (key.trim() == "channel").then(|| value.trim().trim_matches('"').to_string())
+    });
+    channel
+        .or_else(|| {
+            content
+                .lines()
+                .map(str::trim)
+                .find(|l| !l.is_empty() && !l.starts_with('#') && !l.starts_with('['))
+                .map(str::to_string)
+        })
+        .filter(|c| !c.is_empty())
+}
+
+// the installed toolchains, e.g. `1.60.0-x86_64-unknown-linux-gnu`
+fn installed() -> Vec<String> {
+    output(Command::new("rustup").args(["toolchain", "list"]))
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|l| l.split_whitespace().next())
+        .map(str::to_string)
+        .collect()
+}
+
+// the toolchain the tool itself has been run with, including the overrides of `+toolchain`,
+// `RUSTUP_TOOLCHAIN` or the toolchain file of the work tree
+fn current() -> Option<String> {
+    output(Command::new("rustup").args(["show", "active-toolchain"]))?
+        .split_whitespace()
+        .next()
+        .map(str::to_string)
+}
+
+// Select the toolchain declared by a revision exported into the folder when it is installed,
+// or else the current one. Returns `None` when the toolchains are not managed by `rustup`.
+pub fn select(dir: &Path) -> Option<Toolchain> {
+    let installed = installed();
+    let name = declared(dir)
+        .and_then(|channel| {
+            installed
+                .into_iter()
+                .find(|t| *t == channel || t.starts_with(&format!("{channel}-")))
+        })
+        .or_else(current)?;
+    let clippy = output(
+        Command::new("cargo")
+            .arg(format!("+{name}"))
+            .args(["clippy", "--version"])
+            .current_dir(dir),
+    )
+    .unwrap_or_default();
+    Some(Toolchain { name, clippy })
+}
@@ -0,0 +1,409 @@
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use git2::{DiffOptions, Patch};
+use serde::Serialize;
+use tree_sitter::{Node, QueryCursor};
+use tree_sitter_parsers::parse;
+
+use crate::language::Language;
+use crate::Ran;
+
+// the kinds of the items split up from the sources
+const KINDS: [&str; 21] = [
+    "const_item",
+    "macro_invocation",
+    "macro_definition",
+    "empty_statement",
+    "attribute_item",
+    "inner_attribute_item",
+    "mod_item",
+    "foreign_mod_item",
+    "struct_item",
+    "union_item",
+    "enum_item",
+    "type_item",
+    "function_item",
+    "function_signature_item",
+    "impl_item",
+    "trait_item",
+    "associated_type",
+    "let_declaration",
+    "use_declaration",
+    "extern_crate_declaration",
+    "static_item",
+];
+
+// An item of a source file, named after its syntax, e.g. `impl Foo` or `let x`, inside the
+// items of its parent path, e.g. `mod a::impl Foo::bar`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Item {
+    pub kind: String,
+    pub name: String,
+    pub parent: String,
+    pub start_byte: usize,
+    pub end_byte: usize,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl Item {
+    pub fn path(&self) -> String {
+        if self.parent.is_empty() {
+            self.name.clone()
+        } else {
+            format!("{}::{}", self.parent, self.name)
+        }
+    }
+}
+
+fn text<'a>(node: &Node, source: &'a str) -> &'a str {
+    node.utf8_text(source.as_bytes()).unwrap_or("")
+}
+
+fn field<'a>(node: &Node, name: &str, source: &'a str) -> &'a str {
+    node.child_by_field_name(name)
+        .map_or("", |n| text(&n, source))
+}
+
+fn name(node: &Node, source: &str) -> String {
+    match node.kind() {
+        "impl_item" => match node.child_by_field_name("trait") {
+            Some(t) => format!(
+                "impl {} for {}",
+                text(&t, source),
+                field(node, "type", source)
+            ),
+            None => format!("impl {}", field(node, "type", source)),
+        },
+        "let_declaration" => format!("let {}", field(node, "pattern", source)),
+        "use_declaration" => format!("use {}", field(node, "argument", source)),
+        "macro_invocation" => format!("{}!", field(node, "macro", source)),
+        "extern_crate_declaration" => format!("extern crate {}", field(node, "name", source)),
+        "foreign_mod_item" => "extern".to_string(),
+        "attribute_item" | "inner_attribute_item" | "empty_statement" => {
+            text(node, source).to_string()
+        }
+        _ => field(node, "name", source).to_string(),
+    }
+}
+
+// the item of a node, with the names of the items enclosing it as its parent path
+fn item(node: Node, source: &str) -> Item {
+    let mut names = Vec::new();
+    let mut ancestor = node.parent();
+    while let Some(a) = ancestor {
+        if KINDS.contains(&a.kind()) {
+            names.push(name(&a, source));
+        }
+        ancestor = a.parent();
+    }
+    names.reverse();
+    Item {
+        kind: node.kind().to_string(),
+        name: name(&node, source),
+        parent: names.join("::"),
+        start_byte: node.start_byte(),
+        end_byte: node.end_byte(),
+        start_line: node.start_position().row + 1,
+        end_line: node.end_position().row + 1,
+    }
+}
+
+// Split up a source file into its items, outer items first
+pub fn items(source: &str) -> Vec<Item> {
+    let tree = parse(source, "rust");
+    let pattern = KINDS
+        .iter()
+        .map(|k| format!("({k}) @item"))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let Ok(query) = Language::Rust.parse_query(&format!("([\n{pattern}\n])")) else {
+        return Vec::new();
+    };
+    let mut cursor = QueryCursor::new();
+    let mut items: Vec<Item> = cursor
+        .matches(&query, tree.root_node(), source.as_bytes())
+        .flat_map(|m| m.captures)
+        .map(|c| item(c.node, source))
+        .collect();
+    items.sort_by_key(|i| (i.start_byte, std::cmp::Reverse(i.end_byte)));
+    items.dedup_by_key(|i| (i.start_byte, i.end_byte));
+    items
+}
+
+// the identity of an item: its kind, its path and the number of items of the same kind and
+// path before it, which tells apart e.g. the shadowing `let` declarations of a function
+fn identity(items: &[Item], i: usize) -> (&str, String, usize) {
+    let same = |j: &&Item| j.kind == items[i].kind && j.path() == items[i].path();
+    let n = items[..i].iter().filter(same).count();
+    (&items[i].kind, items[i].path(), n)
+}
+
+// The item of `others` with the identity of the item of `items` starting at the byte: the
+// comments marking up a source shift its items but do not change its syntax
+pub fn counterpart<'a>(items: &[Item], others: &'a [Item], start_byte: usize) -> Option<&'a Item> {
+    let i = items.iter().position(|i| i.start_byte == start_byte)?;
+    let id = identity(items, i);
+    (0..others.len())
+        .find(|j| identity(others, *j) == id)
+        .map(|j| &others[j])
+}
+
+// whether an item is inside one of the items
+fn inside(item: &Item, outer: &[&Item]) -> bool {
+    outer
+        .iter()
+        .any(|o| item.parent == o.path() || item.parent.starts_with(&format!("{}::", o.path())))
+}
+
+// Pair the items of a source before and after its transformation by their identities, rather
+// than by their offsets, so that inserting, removing or reordering items does not mismatch the
+// others. The unmatched items are paired with `None` as removed or added, apart from those
+// inside an item removed or added as a whole.
+pub fn pair<'a>(
+    before: &'a [Item],
+    after: &'a [Item],
+) -> Vec<(Option<&'a Item>, Option<&'a Item>)> {
+    let ids: Vec<_> = (0..after.len()).map(|j| identity(after, j)).collect();
+    let mut matched = vec![false; after.len()];
+    let mut pairs = Vec::new();
+    let mut removed: Vec<&Item> = Vec::new();
+    for i in 0..before.len() {
+        let id = identity(before, i);
+        match ids.iter().position(|j| *j == id) {
+            Some(j) => {
+                matched[j] = true;
+                pairs.push((Some(&before[i]), Some(&after[j])));
+            }
+            None if inside(&before[i], &removed) => {}
+            None => {
+                removed.push(&before[i]);
+                pairs.push((Some(&before[i]), None));
+            }
+        }
+    }
+    let mut added: Vec<&Item> = Vec::new();
+    for (j, a) in after.iter().enumerate() {
+        if !matched[j] && !inside(a, &added) {
+            added.push(a);
+            pairs.push((None, Some(a)));
+        }
+    }
+    pairs
+}
+
+// The unified diff of the changes of a file overlapping the lines of one of its items before or
+// after them, which applies to the whole file with `git apply` or `patch -p1`, and the file
+// with only these changes applied
+pub fn unified_diff(
+    file: &str,
+    (input, output): (&[u8], &[u8]),
+    before: Option<&Item>,
+    after: Option<&Item>,
+) -> Result<(String, Vec<u8>), git2::Error> {
+    let overlaps = |item: Option<&Item>, start: u32, lines: u32| {
+        let start = usize::try_from(start).unwrap_or(0);
+        let end = start + usize::try_from(lines).unwrap_or(0);
+        item.is_some_and(|i| start <= i.end_line && end >= i.start_line)
+    };
+    let mut opts = DiffOptions::new();
+    let patch = Patch::from_buffers(
+        input,
+        Some(Path::new(file)),
+        output,
+        Some(Path::new(file)),
+        Some(&mut opts),
+    )?;
+    let old: Vec<&[u8]> = input.split_inclusive(|b| *b == b'\n').collect();
+    // the next line of the input to be copied into the applied file
+    let mut next = 1;
+    let mut applied = Vec::new();
+    let mut diff = String::new();
+    // the lines added less the lines removed by the hunks left out so far, which no longer
+    // shift the new lines of the next hunks
+    let mut dropped: i64 = 0;
+    for h in 0..patch.num_hunks() {
+        let (hunk, lines) = patch.hunk(h)?;
+        if !overlaps(before, hunk.old_start(), hunk.old_lines())
+            && !overlaps(after, hunk.new_start(), hunk.new_lines())
+        {
+            dropped += i64::from(hunk.new_lines()) - i64::from(hunk.old_lines());
+            continue;
+        }
+        if diff.is_empty() {
+            diff.push_str(&format!("--- a/{file}\n+++ b/{file}\n"));
+        }
+        // the header with the new start of the hunk in the file with only the kept hunks
+        // applied, followed by the context of the original header, e.g. `@@ fn main() {`
+        let header = String::from_utf8_lossy(hunk.header());
+        let context = header.splitn(3, "@@").nth(2).unwrap_or("\n");
+        diff.push_str(&format!(
+            "@@ -{},{} +{},{} @@{context}",
+            hunk.old_start(),
+            hunk.old_lines(),
+            i64::from(hunk.new_start()) - dropped,
+            hunk.new_lines()
+        ));
+        // a hunk without old lines is inserted after its old start
+        let old_lines = usize::try_from(hunk.old_lines()).unwrap_or(0);
+        let start = usize::try_from(hunk.old_start()).unwrap_or(0) + usize::from(old_lines == 0);
+        for line in old.iter().take(start.saturating_sub(1)).skip(next - 1) {
+            applied.extend_from_slice(line);
+        }
+        next = start + old_lines;
+        for l in 0..lines {
+            let line = patch.line_in_hunk(h, l)?;
+            let content = String::from_utf8_lossy(line.content());
+            match line.origin() {
+                origin @ (' ' | '+' | '-') => {
+                    diff.push(origin);
+                    diff.push_str(&content);
+                    if origin != '-' {
+                        applied.extend_from_slice(line.content());
+                    }
+                }
+                // the end of a file without a newline
+                _ => diff.push_str(&content),
+            }
+        }
+    }
+    for line in old.iter().skip(next - 1) {
+        applied.extend_from_slice(line);
+    }
+    Ok((diff, applied))
+}
+
+// How a transformation applied on its own to the workspace fares: it compiles, it compiles and
+// passes the tests, or it breaks the build or tests that did not fail without it
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Validation {
+    Compiles,
+    TestsPass,
+    Regressed,
+}
+
+// whether the workspace in the folder compiles, and passes its tests when they are run
+fn check(dir: &Path, tests: bool) -> (bool, bool) {
+    let args = vec!["check".to_string(), "--message-format=json".to_string()];
+    let compiles = crate::to_diagnostic(&mut HashMap::new(), args, dir).success;
+    let passes = compiles
+        && tests
+        && Command::new("cargo")
+            .args(["test", "--quiet"])
+            .current_dir(dir)
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|s| s.success());
+    (compiles, passes)
+}
+
+// The scratch copy of the workspace the transformations are validated on one at a time, and
+// whether its original sources pass their tests
+pub struct Validator {
+    dir: PathBuf,
+    tests: bool,
+    passes: bool,
+}
+
+impl Validator {
+    pub fn new(dir: &Path, tests: bool) -> Validator {
+        let (_, passes) = check(dir, tests);
+        Validator {
+            dir: dir.to_path_buf(),
+            tests,
+            passes,
+        }
+    }
+
+    // validate the file with a transformation applied, restoring its original source afterwards
+    pub fn validate(&self, file: &str, original: &[u8], transformed: &[u8]) -> Validation {
+        let path = self.dir.join(file);
+        if std::fs::write(&path, transformed).is_err() {
+            return Validation::Regressed;
+        }
+        let (compiles, passes) = check(&self.dir, self.tests);
+        std::fs::write(&path, original).ok();
+        match (compiles, passes) {
+            (false, _) => Validation::Regressed,
+            (true, true) => Validation::TestsPass,
+            (true, false) if self.tests && self.passes => Validation::Regressed,
+            (true, false) => Validation::Compiles,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Change {
+    Changed,
+    Added,
+    Removed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Tool {
+    Clippy,
+    Txl,
+    #[serde(rename = "tree-sitter")]
+    TreeSitter,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Warning {
+    pub lint: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+impl Warning {
+    // the warnings overlapping the lines
+    pub fn within(warnings: &[Ran], start_line: usize, end_line: usize) -> Vec<Warning> {
+        warnings
+            .iter()
+            .filter(|w| w.start_line <= end_line && w.end_line >= start_line)
+            .map(|w| Warning {
+                lint: w.lint().to_string(),
+                start_line: w.start_line,
+                end_line: w.end_line,
+            })
+            .collect()
+    }
+}
+
+// A transformation of an item, as a record of the manifest `transform/manifest.jsonl`
+#[derive(Debug, Clone, Serialize)]
+pub struct Record {
+    pub lint: String,
+    pub file: String,
+    // the path of the transformed item, e.g. `impl Foo::bar`, its kind, and whether it has been
+    // changed, or added or removed as a whole
+    pub item: String,
+    pub kind: String,
+    pub change: Change,
+    // the warnings of the item fixed by the transformation, at their lines before it, and
+    // those remaining, at their lines after it
+    pub fixed: Vec<Warning>,
+    pub remaining: Vec<Warning>,
+    pub tool: Tool,
+    // the unified diff of the transformation, and how it fares on its own
+    pub diff: String,
+    pub validation: Validation,
+}
+
+pub fn append(manifest: &Path, record: &Record) -> std::io::Result<()> {
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(manifest)?;
+    if let Ok(s) = serde_json::to_string(record) {
+        writeln!(file, "{s}")?;
+    }
+    Ok(())
+}